@@ -1,10 +1,13 @@
+use serde::Serialize;
 use warp::{
     filters::{body::BodyDeserializeError, cors::CorsForbidden},
     http::StatusCode,
-    reject::Reject,
+    reject::{LengthRequired, PayloadTooLarge, Reject},
+    reply::Response,
     Rejection, Reply,
 };
 use tracing::{event, Level, instrument};
+use utoipa::ToSchema;
 use argon2::Error as ArgonError;
 use reqwest::Error as ReqwestError;
 use reqwest_middleware::Error as MiddlewareReqwestError;
@@ -16,7 +19,32 @@ pub enum Error {
     MissingParameters,
     WrongPassword,
     CannotDecryptToken,
+    Unauthenticated(String),
     Unauthorized,
+    Forbidden(String),
+    NotFound,
+    PreconditionFailed,
+    PreconditionRequired,
+    IdempotencyKeyInProgress,
+    IdempotencyKeyReused,
+    CategoryCycle,
+    CategoryExists,
+    CategoryNotEmpty,
+    OwnProduct,
+    AlreadyReviewed,
+    InvalidToken,
+    EmailNotVerified,
+    InvalidSecondFactor,
+    TwoFactorAlreadyEnabled,
+    TwoFactorNotEnabled,
+    MailError(String),
+    EmptyCart,
+    CartChanged(Vec<i32>),
+    InvalidMoney(String),
+    InvalidConfig(String),
+    CorsOriginForbidden(String),
+    ValidationError(Vec<FieldError>),
+    ExportError(String),
     ArgonLibraryError(ArgonError),
     DatabaseQueryError(sqlx::Error),
     MigrationError(sqlx::migrate::MigrateError),
@@ -26,6 +54,21 @@ pub enum Error {
     ServerError(APILayerError)
 }
 
+/// A single rule broken by a field of a request body
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub rule: String,
+    pub message: String,
+}
+
+/// Body returned along a `400 Bad Request` when a request body fails validation
+#[derive(Debug, Serialize)]
+struct ValidationErrorBody<'a> {
+    message: &'static str,
+    errors: &'a [FieldError],
+}
+
 #[derive(Debug, Clone)]
 pub struct APILayerError {
     pub status: u16,
@@ -40,12 +83,43 @@ impl std::fmt::Display for APILayerError {
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match &*self {
+        match self {
             Error::ParseError(ref err) => write!(f, "Cannot parse parameter: {}", err),
             Error::MissingParameters => write!(f, "Missing parameter"),
             Error::WrongPassword => write!(f, "Wrong password"),
             Error::CannotDecryptToken => write!(f, "Cannot decrypt error"),
+            Error::Unauthenticated(reason) => write!(f, "Not authenticated: {}", reason),
             Error::Unauthorized => write!(f, "No permission to change the underlying resource"),
+            Error::Forbidden(reason) => write!(f, "Forbidden: {}", reason),
+            Error::NotFound => write!(f, "Resource not found"),
+            Error::PreconditionFailed => write!(f, "The resource was modified in the meantime"),
+            Error::PreconditionRequired => write!(f, "Missing If-Match header"),
+            Error::IdempotencyKeyInProgress => write!(f, "A request with this idempotency key is in progress"),
+            Error::IdempotencyKeyReused => write!(f, "The idempotency key was used for another request"),
+            Error::CategoryCycle => write!(f, "A category cannot be moved below itself"),
+            Error::CategoryExists => write!(f, "A category with this name already exists under the same parent"),
+            Error::CategoryNotEmpty => write!(f, "The category has subcategories"),
+            Error::OwnProduct => write!(f, "Sellers cannot buy or review their own products"),
+            Error::AlreadyReviewed => write!(f, "The product was already reviewed by this account, edit the review instead"),
+            Error::EmptyCart => write!(f, "The cart is empty"),
+            Error::CartChanged(products) => {
+                let products: Vec<String> = products.iter().map(|id| id.to_string()).collect();
+                write!(f, "Products changed since they were added to the cart: {}", products.join(", "))
+            }
+            Error::InvalidToken => write!(f, "The token is invalid, expired or already used"),
+            Error::EmailNotVerified => write!(f, "The email address of the account is not verified"),
+            Error::InvalidSecondFactor => write!(f, "The authentication or backup code is invalid"),
+            Error::TwoFactorAlreadyEnabled => write!(f, "Two-factor authentication is already enabled"),
+            Error::TwoFactorNotEnabled => write!(f, "Two-factor authentication is not enabled"),
+            Error::MailError(reason) => write!(f, "Cannot send mail: {}", reason),
+            Error::InvalidMoney(reason) => write!(f, "Invalid amount of money: {}", reason),
+            Error::InvalidConfig(reason) => write!(f, "Invalid configuration: {}", reason),
+            Error::CorsOriginForbidden(origin) => write!(f, "Origin {} is not allowed", origin),
+            Error::ValidationError(errors) => {
+                let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+                write!(f, "Invalid fields: {}", fields.join(", "))
+            }
+            Error::ExportError(reason) => write!(f, "Cannot export data: {}", reason),
             Error::ArgonLibraryError(_) => write!(f, "Cannot verifiy password"),
             Error::DatabaseQueryError(_) => write!(f, "Cannot update, invalid data"),
             Error::MigrationError(_) => write!(f, "Cannot migrate data"),
@@ -63,23 +137,38 @@ impl Reject for APILayerError {}
 const DUPLICATE_KEY: u32 = 23505;
 
 #[instrument]
-pub async fn return_error(r: Rejection) -> Result<impl Reply, Rejection> {
-    if let Some(crate::Error::DatabaseQueryError(e)) = r.find() {
+pub async fn return_error(r: Rejection) -> Result<Response, Rejection> {
+    if let Some(crate::Error::ValidationError(errors)) = r.find() {
+        event!(Level::ERROR, "Request body failed validation: {:?}", errors);
+        let body = ValidationErrorBody {
+            message: "Request body failed validation",
+            errors,
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&body),
+            StatusCode::BAD_REQUEST,
+        ).into_response());
+    }
+
+    if let Some(crate::Error::Unauthenticated(reason)) = r.find() {
+        event!(Level::WARN, "Not authenticated: {}", reason);
+        return Ok(warp::reply::with_header(
+            warp::reply::with_status(reason.clone(), StatusCode::UNAUTHORIZED),
+            "WWW-Authenticate",
+            "Bearer",
+        ).into_response());
+    }
+
+    let reply = if let Some(crate::Error::DatabaseQueryError(e)) = r.find() {
         event!(Level::ERROR, "Database query error");
 
         match e {
-            sqlx::Error::Database(err) => {
-                if err.code().unwrap().parse::<u32>().unwrap() == DUPLICATE_KEY {
-                    Ok(warp::reply::with_status(
-                        "Account already exsists".to_string(),
-                        StatusCode::UNPROCESSABLE_ENTITY,
-                    ))
-                } else {
-                    Ok(warp::reply::with_status(
-                        "Cannot update data".to_string(),
-                        StatusCode::UNPROCESSABLE_ENTITY,
-                    ))
-                }
+            sqlx::Error::Database(err)
+                if err.code().unwrap().parse::<u32>().unwrap() == DUPLICATE_KEY => {
+                Ok(warp::reply::with_status(
+                    "Account already exsists".to_string(),
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                ))
             },
             _ => {
                 Ok(warp::reply::with_status(
@@ -100,6 +189,132 @@ pub async fn return_error(r: Rejection) -> Result<impl Reply, Rejection> {
             "No permission to change underlying resource".to_string(),
             StatusCode::UNAUTHORIZED,
         ))
+    } else if let Some(crate::Error::Forbidden(reason)) = r.find() {
+        event!(Level::WARN, "Forbidden: {}", reason);
+        Ok(warp::reply::with_status(
+            reason.clone(),
+            StatusCode::FORBIDDEN,
+        ))
+    } else if let Some(crate::Error::NotFound) = r.find() {
+        event!(Level::WARN, "Requested resource was not found");
+        Ok(warp::reply::with_status(
+            "Resource not found".to_string(),
+            StatusCode::NOT_FOUND,
+        ))
+    } else if let Some(crate::Error::PreconditionFailed) = r.find() {
+        event!(Level::WARN, "Stale If-Match header");
+        Ok(warp::reply::with_status(
+            "The resource was modified in the meantime, fetch it again".to_string(),
+            StatusCode::PRECONDITION_FAILED,
+        ))
+    } else if let Some(crate::Error::PreconditionRequired) = r.find() {
+        event!(Level::WARN, "Missing If-Match header");
+        Ok(warp::reply::with_status(
+            "Updates require an If-Match header with the ETag of the resource".to_string(),
+            StatusCode::PRECONDITION_REQUIRED,
+        ))
+    } else if let Some(crate::Error::IdempotencyKeyInProgress) = r.find() {
+        event!(Level::WARN, "Concurrent request with the same idempotency key");
+        Ok(warp::reply::with_status(
+            "A request with this Idempotency-Key is still being processed".to_string(),
+            StatusCode::CONFLICT,
+        ))
+    } else if let Some(crate::Error::IdempotencyKeyReused) = r.find() {
+        event!(Level::WARN, "Idempotency key reused for another request");
+        Ok(warp::reply::with_status(
+            "This Idempotency-Key was already used for a different request".to_string(),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ))
+    } else if let Some(crate::Error::CategoryExists) = r.find() {
+        event!(Level::WARN, "Duplicate category name");
+        Ok(warp::reply::with_status(
+            "A category with this name already exists under the same parent".to_string(),
+            StatusCode::CONFLICT,
+        ))
+    } else if let Some(crate::Error::CategoryNotEmpty) = r.find() {
+        event!(Level::WARN, "Deleting a category with subcategories");
+        Ok(warp::reply::with_status(
+            "The category has subcategories, move or delete them first".to_string(),
+            StatusCode::CONFLICT,
+        ))
+    } else if let Some(crate::Error::CategoryCycle) = r.find() {
+        event!(Level::WARN, "Category moved below itself");
+        Ok(warp::reply::with_status(
+            "A category cannot be moved below itself or one of its subcategories".to_string(),
+            StatusCode::CONFLICT,
+        ))
+    } else if let Some(crate::Error::OwnProduct) = r.find() {
+        event!(Level::WARN, "Seller buying or reviewing their own product");
+        Ok(warp::reply::with_status(
+            "Sellers cannot buy or review their own products".to_string(),
+            StatusCode::FORBIDDEN,
+        ))
+    } else if let Some(crate::Error::AlreadyReviewed) = r.find() {
+        event!(Level::WARN, "Product reviewed twice by the same account");
+        Ok(warp::reply::with_status(
+            "The product was already reviewed by this account, edit the review instead".to_string(),
+            StatusCode::CONFLICT,
+        ))
+    } else if let Some(crate::Error::EmptyCart) = r.find() {
+        event!(Level::WARN, "Checkout of an empty cart");
+        Ok(warp::reply::with_status(
+            "The cart is empty, add products before checking out".to_string(),
+            StatusCode::CONFLICT,
+        ))
+    } else if let Some(error @ crate::Error::CartChanged(_)) = r.find() {
+        event!(Level::WARN, "{}", error);
+        Ok(warp::reply::with_status(
+            format!("{}, review the cart before checking out", error),
+            StatusCode::CONFLICT,
+        ))
+    } else if let Some(crate::Error::InvalidToken) = r.find() {
+        event!(Level::WARN, "Invalid, expired or used account token");
+        Ok(warp::reply::with_status(
+            "The token is invalid, expired or already used".to_string(),
+            StatusCode::BAD_REQUEST,
+        ))
+    } else if let Some(crate::Error::EmailNotVerified) = r.find() {
+        event!(Level::WARN, "Unverified account tried to sell");
+        Ok(warp::reply::with_status(
+            "Verify the email address of the account before selling".to_string(),
+            StatusCode::FORBIDDEN,
+        ))
+    } else if let Some(crate::Error::InvalidSecondFactor) = r.find() {
+        event!(Level::WARN, "Invalid second factor");
+        Ok(warp::reply::with_status(
+            "The authentication or backup code is invalid".to_string(),
+            StatusCode::UNAUTHORIZED,
+        ))
+    } else if let Some(crate::Error::TwoFactorAlreadyEnabled) = r.find() {
+        event!(Level::WARN, "Two-factor authentication enabled twice");
+        Ok(warp::reply::with_status(
+            "Two-factor authentication is already enabled, disable it first".to_string(),
+            StatusCode::CONFLICT,
+        ))
+    } else if let Some(crate::Error::TwoFactorNotEnabled) = r.find() {
+        event!(Level::WARN, "Two-factor authentication is not enabled");
+        Ok(warp::reply::with_status(
+            "Two-factor authentication is not enabled".to_string(),
+            StatusCode::BAD_REQUEST,
+        ))
+    } else if let Some(crate::Error::MailError(reason)) = r.find() {
+        event!(Level::ERROR, "Cannot send mail: {}", reason);
+        Ok(warp::reply::with_status(
+            "Internal Server Error".to_string(),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ))
+    } else if let Some(crate::Error::InvalidMoney(reason)) = r.find() {
+        event!(Level::ERROR, "Invalid amount of money: {}", reason);
+        Ok(warp::reply::with_status(
+            format!("Invalid amount of money: {}", reason),
+            StatusCode::BAD_REQUEST,
+        ))
+    } else if let Some(crate::Error::ExportError(reason)) = r.find() {
+        event!(Level::ERROR, "Cannot export data: {}", reason);
+        Ok(warp::reply::with_status(
+            "Internal Server Error".to_string(),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ))
     } else if let Some(crate::Error::WrongPassword) = r.find() {
         event!(Level::ERROR, "Entered wrong password");
         Ok(warp::reply::with_status(
@@ -124,12 +339,30 @@ pub async fn return_error(r: Rejection) -> Result<impl Reply, Rejection> {
             "Internal Server Error".to_string(),
             StatusCode::INTERNAL_SERVER_ERROR,
         ))
+    } else if let Some(error @ crate::Error::CorsOriginForbidden(_)) = r.find() {
+        event!(Level::WARN, "CORS forbidden error: {}", error);
+        Ok(warp::reply::with_status(
+            error.to_string(),
+            StatusCode::FORBIDDEN,
+        ))
     } else if let Some(error) = r.find::<CorsForbidden>() {
         event!(Level::ERROR, "CORS forbidden error: {}", error);
         Ok(warp::reply::with_status(
             error.to_string(),
             StatusCode::FORBIDDEN,
         ))
+    } else if let Some(error) = r.find::<PayloadTooLarge>() {
+        event!(Level::ERROR, "Request body too large: {}", error);
+        Ok(warp::reply::with_status(
+            error.to_string(),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        ))
+    } else if let Some(error) = r.find::<LengthRequired>() {
+        event!(Level::ERROR, "Request body without length: {}", error);
+        Ok(warp::reply::with_status(
+            error.to_string(),
+            StatusCode::LENGTH_REQUIRED,
+        ))
     } else if let Some(error) = r.find::<BodyDeserializeError>() {
         event!(Level::ERROR, "Cannot deserizalize request body: {}", error);
         Ok(warp::reply::with_status(
@@ -148,5 +381,7 @@ pub async fn return_error(r: Rejection) -> Result<impl Reply, Rejection> {
             "Route not found".to_string(),
             StatusCode::NOT_FOUND,
         ))
-    }
+    };
+
+    reply.map(Reply::into_response)
 }
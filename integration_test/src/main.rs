@@ -1,5 +1,9 @@
 use futures_util::FutureExt;
 use restful_api::{config, handle_errors, oneshot, setup_store};
+use restful_api::types::accounts::AccountId;
+use restful_api::types::idempotency::request_hash;
+use restful_api::types::money::Money;
+use restful_api::types::products::NewProducts;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::process::Command;
@@ -12,21 +16,25 @@ struct User {
     username: String,
     password: String,
     role: String,
+    email: String,
 }
 #[derive(Serialize, Deserialize, Debug, Clone)]
-struct Token(String);
+struct Token {
+    access_token: String,
+    token_type: String,
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct Product {
     name: String,
-    price: i32,
+    price: Money,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct ProductSell {
     id: i32,
     name: String,
-    price: i32,
+    price: Money,
 }
 
 #[tokio::main]
@@ -58,17 +66,21 @@ async fn main() -> Result<(), handle_errors::Error> {
 
     io::stdout().write_all(&s.stderr).unwrap();
 
+    //Mails of earlier runs would be mistaken for the ones of this run
+    let _ = std::fs::remove_dir_all(&config.mail_dir);
+
     //Set up a new store instance with a db connection pool
     let store = setup_store(&config).await?;
 
     //start the server and listen for a sender signal to shut it down
-    let handler = oneshot(store).await;
+    let handler = oneshot(&config, store.clone()).await;
 
     //Create a user throughout the test
     let u = User {
         username: "username".to_string(),
         password: "password".to_string(),
-        role: "user".to_string()
+        role: "user".to_string(),
+        email: "username@example.com".to_string()
     };
 
     let token;
@@ -82,6 +94,15 @@ async fn main() -> Result<(), handle_errors::Error> {
             std::process::exit(1);
         }
     }
+    print!("Running verify_email ...");
+    match std::panic::AssertUnwindSafe(verify_email(&config.mail_dir, &u)).catch_unwind().await {
+        Ok(_) => println!("{color_green} Test pass ✓{color_reset}"),
+        Err(_) => {
+            let _ = handler.sender.send(1);
+            std::process::exit(1);
+        }
+    }
+
     print!("Running login ...");
     match std::panic::AssertUnwindSafe(login(u)).catch_unwind().await {
         Ok(t) => {
@@ -95,7 +116,48 @@ async fn main() -> Result<(), handle_errors::Error> {
     }
 
     print!("Running add_product ...");
-    match std::panic::AssertUnwindSafe(add_product(token)).catch_unwind().await {
+    match std::panic::AssertUnwindSafe(add_product(token.clone())).catch_unwind().await {
+        Ok(_) => println!("{color_green} Test pass ✓{color_reset}"),
+        Err(_) => {
+            let _ = handler.sender.send(1);
+            std::process::exit(1);
+        }
+    }
+
+    print!("Running export_and_import ...");
+    match std::panic::AssertUnwindSafe(export_and_import(token.clone())).catch_unwind().await {
+        Ok(_) => println!("{color_green} Test pass ✓{color_reset}"),
+        Err(_) => {
+            let _ = handler.sender.send(1);
+            std::process::exit(1);
+        }
+    }
+
+    print!("Running idempotent_requests ...");
+    //A request in progress on another instance, it holds its key until it completes
+    let in_flight = NewProducts { name: "in flight".to_string(), description: String::new(), price: Money::new(700, "USD") };
+    store
+        .begin_idempotent_request(&AccountId(1), "in-flight", &request_hash("POST /products", &in_flight))
+        .await?;
+    match std::panic::AssertUnwindSafe(idempotent_requests(token.clone(), in_flight)).catch_unwind().await {
+        Ok(_) => println!("{color_green} Test pass ✓{color_reset}"),
+        Err(_) => {
+            let _ = handler.sender.send(1);
+            std::process::exit(1);
+        }
+    }
+
+    print!("Running update_price ...");
+    match std::panic::AssertUnwindSafe(update_price(token.clone())).catch_unwind().await {
+        Ok(_) => println!("{color_green} Test pass ✓{color_reset}"),
+        Err(_) => {
+            let _ = handler.sender.send(1);
+            std::process::exit(1);
+        }
+    }
+
+    print!("Running refused_requests ...");
+    match std::panic::AssertUnwindSafe(refused_requests(token)).catch_unwind().await {
         Ok(_) => println!("{color_green} Test pass ✓{color_reset}"),
         Err(_) => {
             let _ = handler.sender.send(1);
@@ -109,7 +171,7 @@ async fn main() -> Result<(), handle_errors::Error> {
 async fn register_new_user(user: &User) {
     let client = reqwest::Client::new();
     let res = client
-        .post("http://localhost:3030/registration")
+        .post("http://localhost:3030/v1/registration")
         .json(&user)
         .send()
         .await
@@ -120,10 +182,32 @@ async fn register_new_user(user: &User) {
     assert_eq!(res, "Account added".to_string());
 }
 
+async fn verify_email(mail_dir: &str, user: &User) {
+    //The file mailer writes one file per mail, the token is on its own line
+    let mail = std::fs::read_dir(mail_dir)
+        .unwrap()
+        .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+        .find(|mail| mail.contains(&format!("To: {}", user.email)))
+        .expect("verification mail not sent");
+    let token = mail
+        .lines()
+        .find_map(|line| line.strip_prefix("Token: "))
+        .expect("token not found in mail");
+
+    let client = reqwest::Client::new();
+    let res = client
+        .post("http://localhost:3030/v1/email/verify")
+        .json(&serde_json::json!({ "token": token }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+}
+
 async fn login(user: User) -> Token{
     let client = reqwest::Client::new();
     let res = client
-        .post("http://localhost:3030/login")
+        .post("http://localhost:3030/v1/login")
         .json(&user)
         .send()
         .await
@@ -136,12 +220,12 @@ async fn login(user: User) -> Token{
 async fn add_product(token: Token) {
     let p = Product {
         name: "sample".to_string(),
-        price: 10
+        price: Money::new(1000, "USD")
     };
     let client = reqwest::Client::new();
     let res = client
-        .post("http://localhost:3030/products")
-        .header("Authorization", token.0)
+        .post("http://localhost:3030/v1/products")
+        .header("Authorization", format!("{} {}", token.token_type, token.access_token))
         .json(&p)
         .send()
         .await
@@ -151,4 +235,162 @@ async fn add_product(token: Token) {
         .unwrap();
     assert_eq!(res.id, 1);
     assert_eq!(res.name, p.name);
-}
\ No newline at end of file
+    assert_eq!(res.price, p.price);
+}
+
+async fn export_and_import(token: Token) {
+    let authorization = format!("{} {}", token.token_type, token.access_token);
+    let client = reqwest::Client::new();
+    let res = client
+        .put("http://localhost:3030/v1/products/1/tags")
+        .header("Authorization", &authorization)
+        .json(&vec!["Kitchen", "gift"])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+
+    let csv = client
+        .get("http://localhost:3030/v1/products/export?format=csv")
+        .header("Authorization", &authorization)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(csv.contains("1,sample,,1000,USD,,gift|kitchen"));
+
+    //Rows carrying an id update their product instead of duplicating it
+    let edited = csv.replace("1,sample,,1000", "1,sample,,1200");
+    let res = client
+        .post("http://localhost:3030/v1/products/import?format=csv")
+        .header("Authorization", &authorization)
+        .body(edited)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 200);
+
+    let products = client
+        .get("http://localhost:3030/v1/products")
+        .send()
+        .await
+        .unwrap()
+        .json::<Value>()
+        .await
+        .unwrap();
+    let products = products.as_array().unwrap();
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0]["price"]["amount"], 1200);
+    assert_eq!(products[0]["tags"], serde_json::json!(["gift", "kitchen"]));
+}
+
+async fn idempotent_requests(token: Token, in_flight: NewProducts) {
+    let authorization = format!("{} {}", token.token_type, token.access_token);
+    let client = reqwest::Client::new();
+    let post = |key: &str, name: &str| {
+        client
+            .post("http://localhost:3030/v1/products")
+            .header("Authorization", &authorization)
+            .header("Idempotency-Key", key)
+            .json(&Product { name: name.to_string(), price: Money::new(700, "USD") })
+            .send()
+    };
+
+    let first = post("retried", "retried").await.unwrap();
+    assert_eq!(first.status(), 200);
+    assert!(first.headers().get("idempotent-replayed").is_none());
+    let first = first.json::<ProductSell>().await.unwrap();
+
+    let replayed = post("retried", "retried").await.unwrap();
+    assert_eq!(replayed.status(), 200);
+    assert_eq!(replayed.headers()["idempotent-replayed"], "true");
+    assert_eq!(replayed.json::<ProductSell>().await.unwrap().id, first.id);
+
+    //The same key with another body
+    let reused = post("retried", "another").await.unwrap();
+    assert_eq!(reused.status(), 422);
+
+    let in_progress = post("in-flight", &in_flight.name).await.unwrap();
+    assert_eq!(in_progress.status(), 409);
+}
+
+async fn update_price(token: Token) {
+    let authorization = format!("{} {}", token.token_type, token.access_token);
+    let client = reqwest::Client::new();
+    let created = client
+        .post("http://localhost:3030/v1/products")
+        .header("Authorization", &authorization)
+        .json(&Product { name: "in euros".to_string(), price: Money::new(900, "EUR") })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(created.status(), 200);
+    let etag = created.headers()["etag"].clone();
+    let created = created.json::<ProductSell>().await.unwrap();
+
+    //A price without a currency keeps the one of the product
+    let updated = client
+        .patch(format!("http://localhost:3030/v1/products/{}", created.id))
+        .header("Authorization", &authorization)
+        .header("If-Match", etag)
+        .json(&serde_json::json!({"price": {"amount": 500}}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(updated.status(), 200);
+    assert_eq!(updated.json::<ProductSell>().await.unwrap().price, Money::new(500, "EUR"));
+}
+
+async fn refused_requests(token: Token) {
+    let client = reqwest::Client::new();
+    let review = serde_json::json!({ "rating": 4, "body": "sturdy" });
+
+    //The seller of product 1
+    let seller = format!("{} {}", token.token_type, token.access_token);
+    let res = client
+        .post("http://localhost:3030/v1/products/1/reviews")
+        .header("Authorization", &seller)
+        .json(&review)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 403);
+    let res = client
+        .put("http://localhost:3030/v1/cart/items/1")
+        .header("Authorization", &seller)
+        .json(&serde_json::json!({ "quantity": 1 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 403);
+
+    let buyer = User {
+        username: "buyer".to_string(),
+        password: "password".to_string(),
+        role: "user".to_string(),
+        email: "buyer@example.com".to_string()
+    };
+    register_new_user(&buyer).await;
+    let token = login(buyer).await;
+    let buyer = format!("{} {}", token.token_type, token.access_token);
+    for expected in [200, 409] {
+        let res = client
+            .post("http://localhost:3030/v1/products/1/reviews")
+            .header("Authorization", &buyer)
+            .json(&review)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), expected);
+    }
+
+    let res = client
+        .post("http://localhost:3030/v1/cart/checkout")
+        .header("Authorization", &buyer)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 409);
+}
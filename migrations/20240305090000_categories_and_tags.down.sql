@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS product_tags;
+DROP TABLE IF EXISTS tags;
+ALTER TABLE products DROP COLUMN IF EXISTS category_id;
+DROP TABLE IF EXISTS categories;
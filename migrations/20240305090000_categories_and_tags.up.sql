@@ -0,0 +1,20 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS categories (
+    id serial PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    parent_id INT REFERENCES categories,
+    UNIQUE (parent_id, name)
+);
+
+ALTER TABLE products ADD COLUMN IF NOT EXISTS category_id INT REFERENCES categories ON DELETE SET NULL;
+
+CREATE TABLE IF NOT EXISTS tags (
+    id serial PRIMARY KEY,
+    name VARCHAR(64) NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS product_tags (
+    product_id INT NOT NULL REFERENCES products ON DELETE CASCADE,
+    tag_id INT NOT NULL REFERENCES tags ON DELETE CASCADE,
+    PRIMARY KEY (product_id, tag_id)
+);
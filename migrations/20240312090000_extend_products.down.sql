@@ -0,0 +1,6 @@
+-- Add down migration script here
+ALTER TABLE products
+    DROP COLUMN IF EXISTS description,
+    DROP COLUMN IF EXISTS currency,
+    DROP COLUMN IF EXISTS created_on,
+    DROP COLUMN IF EXISTS updated_on;
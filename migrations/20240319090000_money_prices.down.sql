@@ -0,0 +1,10 @@
+-- Add down migration script here
+ALTER TABLE products DROP CONSTRAINT IF EXISTS products_price_amount_check;
+ALTER TABLE products ALTER COLUMN price_amount TYPE INT USING (price_amount / CASE
+    WHEN upper(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG',
+                             'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN upper(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    WHEN upper(currency) IN ('CLF', 'UYW') THEN 10000
+    ELSE 100
+END)::INT;
+ALTER TABLE products RENAME COLUMN price_amount TO price;
@@ -0,0 +1,12 @@
+-- Add up migration script here
+-- Prices used to be whole units of the currency, they are now stored in its minor unit,
+-- scaled by the ISO 4217 exponent of each currency, e.g. 0 for JPY and 3 for KWD
+ALTER TABLE products RENAME COLUMN price TO price_amount;
+ALTER TABLE products ALTER COLUMN price_amount TYPE BIGINT USING price_amount::BIGINT * CASE
+    WHEN upper(currency) IN ('BIF', 'CLP', 'DJF', 'GNF', 'ISK', 'JPY', 'KMF', 'KRW', 'PYG',
+                             'RWF', 'UGX', 'UYI', 'VND', 'VUV', 'XAF', 'XOF', 'XPF') THEN 1
+    WHEN upper(currency) IN ('BHD', 'IQD', 'JOD', 'KWD', 'LYD', 'OMR', 'TND') THEN 1000
+    WHEN upper(currency) IN ('CLF', 'UYW') THEN 10000
+    ELSE 100
+END;
+ALTER TABLE products ADD CONSTRAINT products_price_amount_check CHECK (price_amount >= 0);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS products_live_idx;
+ALTER TABLE products DROP COLUMN IF EXISTS deleted_at;
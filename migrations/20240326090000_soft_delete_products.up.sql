@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE products ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+CREATE INDEX IF NOT EXISTS products_live_idx ON products (id) WHERE deleted_at IS NULL;
@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE products ADD COLUMN IF NOT EXISTS version INT NOT NULL DEFAULT 1;
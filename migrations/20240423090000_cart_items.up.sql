@@ -0,0 +1,12 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS cart_items (
+    account_id INT NOT NULL REFERENCES accounts ON DELETE CASCADE,
+    product_id INT NOT NULL REFERENCES products ON DELETE CASCADE,
+    quantity INT NOT NULL CHECK (quantity > 0),
+    -- Price of the product when the quantity was last set, checked again at checkout
+    unit_price_amount BIGINT NOT NULL,
+    unit_price_currency CHAR(3) NOT NULL,
+    added_on TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_on TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (account_id, product_id)
+);
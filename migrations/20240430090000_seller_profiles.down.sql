@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE accounts
+    DROP COLUMN IF EXISTS display_name,
+    DROP COLUMN IF EXISTS bio,
+    ALTER COLUMN created_on TYPE TIMESTAMP USING created_on AT TIME ZONE 'UTC';
@@ -0,0 +1,11 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS reviews (
+    id serial PRIMARY KEY,
+    product_id INT NOT NULL REFERENCES products ON DELETE CASCADE,
+    author_id INT NOT NULL REFERENCES accounts ON DELETE CASCADE,
+    rating SMALLINT NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    body TEXT NOT NULL DEFAULT '',
+    created_on TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_on TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (product_id, author_id)
+);
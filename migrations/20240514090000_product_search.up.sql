@@ -0,0 +1,8 @@
+-- Add up migration script here
+-- Names weigh more than descriptions when ranking search results
+ALTER TABLE products ADD COLUMN IF NOT EXISTS search_vector tsvector
+    GENERATED ALWAYS AS (
+        setweight(to_tsvector('english', coalesce(name, '')), 'A') ||
+        setweight(to_tsvector('english', coalesce(description, '')), 'B')
+    ) STORED;
+CREATE INDEX IF NOT EXISTS products_search_vector_idx ON products USING GIN (search_vector);
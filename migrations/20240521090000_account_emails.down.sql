@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS account_tokens;
+DROP INDEX IF EXISTS accounts_email_idx;
+ALTER TABLE accounts
+    DROP COLUMN IF EXISTS email,
+    DROP COLUMN IF EXISTS email_verified_on;
@@ -0,0 +1,17 @@
+-- Add up migration script here
+ALTER TABLE accounts
+    ADD COLUMN IF NOT EXISTS email VARCHAR(255),
+    ADD COLUMN IF NOT EXISTS email_verified_on TIMESTAMPTZ;
+CREATE UNIQUE INDEX IF NOT EXISTS accounts_email_idx ON accounts (email);
+-- Accounts registered before verification existed keep selling
+UPDATE accounts SET email_verified_on = NOW() WHERE email_verified_on IS NULL;
+
+CREATE TABLE IF NOT EXISTS account_tokens (
+    id serial PRIMARY KEY,
+    account_id INT NOT NULL REFERENCES accounts ON DELETE CASCADE,
+    purpose VARCHAR(32) NOT NULL,
+    token_hash CHAR(64) NOT NULL UNIQUE,
+    expires_on TIMESTAMPTZ NOT NULL,
+    used_on TIMESTAMPTZ,
+    created_on TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
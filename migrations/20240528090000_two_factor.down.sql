@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS totp_backup_codes;
+ALTER TABLE accounts
+    DROP COLUMN IF EXISTS totp_secret,
+    DROP COLUMN IF EXISTS totp_enabled_on,
+    DROP COLUMN IF EXISTS totp_last_step;
@@ -0,0 +1,14 @@
+-- Add up migration script here
+ALTER TABLE accounts
+    ADD COLUMN IF NOT EXISTS totp_secret VARCHAR(64),
+    ADD COLUMN IF NOT EXISTS totp_enabled_on TIMESTAMPTZ,
+    -- Time step of the last accepted code, a code is only accepted once
+    ADD COLUMN IF NOT EXISTS totp_last_step BIGINT;
+
+CREATE TABLE IF NOT EXISTS totp_backup_codes (
+    id serial PRIMARY KEY,
+    account_id INT NOT NULL REFERENCES accounts ON DELETE CASCADE,
+    code_hash CHAR(64) NOT NULL,
+    used_on TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS totp_backup_codes_account_idx ON totp_backup_codes (account_id);
@@ -0,0 +1,2 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS categories_root_name_key;
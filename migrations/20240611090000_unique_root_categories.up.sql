@@ -0,0 +1,8 @@
+-- Add up migration script here
+-- UNIQUE (parent_id, name) lets root categories share a name, as NULL parents are distinct.
+-- Earlier duplicates are renamed after their id before the names are made unique
+UPDATE categories c SET name = c.name || ' (' || c.id || ')'
+WHERE c.parent_id IS NULL
+AND EXISTS (SELECT 1 FROM categories o WHERE o.parent_id IS NULL AND o.name = c.name AND o.id < c.id);
+
+CREATE UNIQUE INDEX IF NOT EXISTS categories_root_name_key ON categories (name) WHERE parent_id IS NULL;
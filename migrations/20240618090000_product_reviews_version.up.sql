@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- Bumped when the reviews of a product change, `version` only moves with the edits of the seller
+ALTER TABLE products ADD COLUMN IF NOT EXISTS reviews_version INT NOT NULL DEFAULT 1;
@@ -0,0 +1,195 @@
+use std::io;
+
+use async_compression::tokio::bufread::{BrotliEncoder, GzipEncoder};
+use async_compression::Level;
+use futures_util::TryStreamExt;
+use tokio_util::io::{ReaderStream, StreamReader};
+use warp::http::header::{HeaderMap, HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, VARY};
+use warp::http::StatusCode;
+use warp::hyper::body::HttpBody;
+use warp::hyper::Body;
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+/// Replies smaller than this aren't worth the CPU, the headers outweigh the savings
+pub const MIN_COMPRESSED_SIZE: u64 = 1024;
+/// Brotli quality of the replies, the maximum of 11 is meant for static assets
+const BROTLI_QUALITY: i32 = 4;
+
+/// Content codings the API can reply with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    /// Pick the coding of a reply from `Accept-Encoding`, highest quality first and brotli on
+    /// ties. `*` stands for the codings the header doesn't list, `q=0` refuses a coding
+    /// # Example usage
+    /// ```rust
+    /// use restful_api::compression::Encoding;
+    ///
+    /// assert_eq!(Encoding::negotiate("gzip, deflate, br"), Some(Encoding::Brotli));
+    /// assert_eq!(Encoding::negotiate("br;q=0.5, gzip"), Some(Encoding::Gzip));
+    /// assert_eq!(Encoding::negotiate("identity"), None);
+    /// ```
+    pub fn negotiate(accept_encoding: &str) -> Option<Self> {
+        let mut any = None;
+        let mut listed: Vec<(&str, f32)> = Vec::new();
+        for item in accept_encoding.split(',') {
+            let mut params = item.split(';').map(str::trim);
+            let coding = params.next().unwrap_or_default();
+            let quality = params
+                .find_map(|param| param.strip_prefix("q="))
+                .map(|q| q.parse::<f32>().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            if coding == "*" {
+                any = Some(quality);
+            } else if !coding.is_empty() {
+                listed.push((coding, quality));
+            }
+        }
+
+        let quality = |encoding: Encoding| {
+            listed
+                .iter()
+                .find(|(coding, _)| coding.eq_ignore_ascii_case(encoding.as_str()))
+                .map(|(_, quality)| *quality)
+                .or(any)
+                .unwrap_or(0.0)
+        };
+        [Encoding::Brotli, Encoding::Gzip]
+            .into_iter()
+            .map(|encoding| (encoding, quality(encoding)))
+            .filter(|(_, quality)| *quality > 0.0)
+            .fold(None, |best: Option<(Encoding, f32)>, candidate| match best {
+                Some(best) if best.1 >= candidate.1 => Some(best),
+                _ => Some(candidate),
+            })
+            .map(|(encoding, _)| encoding)
+    }
+}
+
+/// Text replies compress well, anything else is sent as is. Event streams aren't compressed,
+/// the encoder would hold the events back until it has filled a block
+fn is_compressible(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| {
+            content_type.starts_with("application/json")
+                || content_type.starts_with("application/x-ndjson")
+                || (content_type.starts_with("text/") && !content_type.starts_with("text/event-stream"))
+        })
+}
+
+/*
+@desc Compress a reply with the negotiated coding. The body is compressed as it streams,
+so catalog exports aren't buffered. Replies that vary on the coding say so in `Vary`
+ */
+pub fn compress(response: Response, accept_encoding: Option<&str>) -> Response {
+    let (mut parts, body) = response.into_parts();
+    if !is_compressible(&parts.headers)
+        || parts.headers.contains_key(CONTENT_ENCODING)
+        || parts.status == StatusCode::NO_CONTENT
+        || parts.status == StatusCode::NOT_MODIFIED
+    {
+        return Response::from_parts(parts, body);
+    }
+    parts.headers.append(VARY, HeaderValue::from_static("accept-encoding"));
+
+    let small = HttpBody::size_hint(&body)
+        .exact()
+        .is_some_and(|length| length < MIN_COMPRESSED_SIZE);
+    let encoding = match accept_encoding.and_then(Encoding::negotiate) {
+        Some(encoding) if !small => encoding,
+        _ => return Response::from_parts(parts, body),
+    };
+
+    let reader = StreamReader::new(TryStreamExt::map_err(body, io::Error::other));
+    let body = match encoding {
+        Encoding::Brotli => Body::wrap_stream(ReaderStream::new(BrotliEncoder::with_quality(
+            reader,
+            Level::Precise(BROTLI_QUALITY),
+        ))),
+        Encoding::Gzip => Body::wrap_stream(ReaderStream::new(GzipEncoder::new(reader))),
+    };
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.headers.insert(CONTENT_ENCODING, HeaderValue::from_static(encoding.as_str()));
+    Response::from_parts(parts, body)
+}
+
+/// Compress the replies of `routes` when `enabled`, errors recovered by `routes` included
+pub fn negotiated<F, R>(
+    enabled: bool,
+    routes: F,
+) -> impl Filter<Extract = (Response,), Error = warp::Rejection> + Clone
+where
+    F: Filter<Extract = (R,), Error = warp::Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    warp::header::headers_cloned()
+        .and(routes)
+        .map(move |headers: HeaderMap, reply: R| {
+            let response = reply.into_response();
+            if !enabled {
+                return response;
+            }
+            let accept_encoding = headers.get("accept-encoding").and_then(|value| value.to_str().ok());
+            compress(response, accept_encoding)
+        })
+}
+
+#[cfg(test)]
+mod compression_test {
+    use super::{compress, Encoding, Reply, MIN_COMPRESSED_SIZE};
+    use async_compression::tokio::bufread::GzipDecoder;
+    use tokio::io::AsyncReadExt;
+
+    #[test]
+    fn negotiation() {
+        assert_eq!(Encoding::negotiate("gzip;q=1.0, br;q=0.8"), Some(Encoding::Gzip));
+        assert_eq!(Encoding::negotiate("*"), Some(Encoding::Brotli));
+        assert_eq!(Encoding::negotiate("br;q=0, *"), Some(Encoding::Gzip));
+        assert_eq!(Encoding::negotiate("GZIP"), Some(Encoding::Gzip));
+        assert_eq!(Encoding::negotiate("gzip;q=0, br;q=0"), None);
+        assert_eq!(Encoding::negotiate(""), None);
+    }
+
+    #[tokio::test]
+    async fn compressed_round_trip() {
+        let products: Vec<_> = (0..100).map(|id| serde_json::json!({ "id": id, "name": "mug" })).collect();
+        let json = serde_json::to_string(&products).unwrap();
+        assert!(json.len() as u64 > MIN_COMPRESSED_SIZE);
+        let response = compress(warp::reply::json(&products).into_response(), Some("gzip"));
+        assert_eq!(response.headers()["content-encoding"], "gzip");
+        assert_eq!(response.headers()["vary"], "accept-encoding");
+
+        let body = warp::hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(body.len() < json.len());
+        let mut decoded = String::new();
+        GzipDecoder::new(&body[..]).read_to_string(&mut decoded).await.unwrap();
+        assert_eq!(decoded, json);
+    }
+
+    #[tokio::test]
+    async fn small_and_binary_replies_are_left_alone() {
+        let response = compress(warp::reply::json(&"mug").into_response(), Some("gzip"));
+        assert!(response.headers().get("content-encoding").is_none());
+        assert_eq!(response.headers()["vary"], "accept-encoding");
+
+        let response = compress(
+            warp::reply::with_header(vec![0u8; 4096], "content-type", "image/png").into_response(),
+            Some("gzip"),
+        );
+        assert!(response.headers().get("content-encoding").is_none());
+    }
+}
@@ -0,0 +1,166 @@
+use warp::http::header::{HeaderMap, HeaderValue, CACHE_CONTROL, ETAG, LAST_MODIFIED};
+use warp::http::{Method, StatusCode};
+use warp::hyper::Body;
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+use handle_errors::Error;
+
+use crate::config::Config;
+use crate::types::etag::{content_etag, parse_http_date, IfNoneMatch};
+
+/// Headers of a `200 OK` kept on the `304 Not Modified` answering it, as per RFC 9110
+const NOT_MODIFIED_HEADERS: [&str; 5] = ["cache-control", "etag", "expires", "last-modified", "vary"];
+
+/// Caching of the public catalog replies, read from the configuration
+#[derive(Debug, Clone)]
+pub struct CatalogCaching {
+    cache_control: HeaderValue,
+}
+
+impl CatalogCaching {
+    pub fn from_config(config: &Config) -> Result<Self, Error> {
+        let cache_control = HeaderValue::from_str(config.catalog_cache_control.trim()).map_err(|_| {
+            Error::InvalidConfig(format!(
+                "Catalog Cache-Control '{}' is not a header value",
+                config.catalog_cache_control
+            ))
+        })?;
+        Ok(CatalogCaching { cache_control })
+    }
+
+    /*
+    @desc Answer the GET requests of `routes` with `Cache-Control`, an `ETag` derived from the
+    body when the handler set no validator, and `304 Not Modified` when the client's copy is current
+     */
+    pub fn conditional<F, R>(
+        &self,
+        routes: F,
+    ) -> impl Filter<Extract = (Response,), Error = warp::Rejection> + Clone
+    where
+        F: Filter<Extract = (R,), Error = warp::Rejection> + Clone + Send + Sync + 'static,
+        R: Reply,
+    {
+        let cache_control = self.cache_control.clone();
+        warp::method()
+            .and(warp::header::headers_cloned())
+            .and(routes)
+            .and_then(move |method: Method, request: HeaderMap, reply: R| {
+                let cache_control = cache_control.clone();
+                async move {
+                    let mut response = reply.into_response();
+                    if method != Method::GET || response.status() != StatusCode::OK {
+                        return Ok::<_, warp::Rejection>(response);
+                    }
+                    response.headers_mut().insert(CACHE_CONTROL, cache_control);
+                    Ok(revalidate(response, &request).await)
+                }
+            })
+    }
+}
+
+/*
+@desc Turn a `200 OK` into a `304 Not Modified` when `If-None-Match` holds its entity tag or,
+for a reply without entity tag, when `If-Modified-Since` isn't older than its `Last-Modified`.
+`Last-Modified` only has a one second precision, so the entity tag wins when there is one
+ */
+pub async fn revalidate(response: Response, request: &HeaderMap) -> Response {
+    let (mut parts, body) = response.into_parts();
+    let (etag, body) = match parts.headers.get(ETAG) {
+        Some(etag) => (Some(etag.to_str().unwrap_or_default().to_string()), body),
+        None if parts.headers.contains_key(LAST_MODIFIED) => (None, body),
+        None => {
+            let bytes = match warp::hyper::body::to_bytes(body).await {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "Cannot read reply body: {:?}", e);
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
+            };
+            let etag = content_etag(&bytes);
+            parts.headers.insert(ETAG, HeaderValue::from_str(&etag).expect("Entity tags are header values"));
+            (Some(etag), Body::from(bytes))
+        }
+    };
+    not_modified_or(Response::from_parts(parts, body), etag.as_deref(), request)
+}
+
+fn not_modified_or(response: Response, etag: Option<&str>, request: &HeaderMap) -> Response {
+    let header = |name: &str| request.get(name).and_then(|value| value.to_str().ok());
+
+    let not_modified = match etag {
+        Some(etag) => header("if-none-match").is_some_and(|if_none_match| IfNoneMatch::parse(if_none_match).matches(etag)),
+        None => {
+            let last_modified = response
+                .headers()
+                .get(LAST_MODIFIED)
+                .and_then(|value| parse_http_date(value.to_str().ok()?));
+            let if_modified_since = header("if-modified-since").and_then(parse_http_date);
+            matches!((last_modified, if_modified_since), (Some(modified), Some(since)) if modified <= since)
+        }
+    };
+    if !not_modified {
+        return response;
+    }
+
+    let mut not_modified = StatusCode::NOT_MODIFIED.into_response();
+    for name in NOT_MODIFIED_HEADERS {
+        for value in response.headers().get_all(name) {
+            not_modified.headers_mut().append(name, value.clone());
+        }
+    }
+    not_modified
+}
+
+#[cfg(test)]
+mod conditional_test {
+    use super::{revalidate, HeaderMap, Reply, StatusCode};
+    use crate::types::etag::{http_date, product_etag};
+    use chrono::{Duration, Utc};
+
+    fn request(name: &'static str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, value.parse().unwrap());
+        headers
+    }
+
+    #[tokio::test]
+    async fn content_etag_and_if_none_match() {
+        let response = revalidate(warp::reply::json(&vec![1, 2]).into_response(), &HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()["etag"].to_str().unwrap().to_string();
+
+        let response = revalidate(warp::reply::json(&vec![1, 2]).into_response(), &request("if-none-match", &etag)).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()["etag"], etag.as_str());
+
+        let response = revalidate(warp::reply::json(&vec![1, 3]).into_response(), &request("if-none-match", &etag)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn handler_etag_and_if_modified_since() {
+        let modified = Utc::now() - Duration::hours(1);
+        let product = || {
+            warp::reply::with_header(
+                warp::reply::with_header(warp::reply::json(&"mug"), "etag", product_etag(3, 1)),
+                "last-modified",
+                http_date(&modified),
+            )
+            .into_response()
+        };
+
+        let response = revalidate(product(), &request("if-none-match", &product_etag(3, 1))).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        //The entity tag may change within the second of Last-Modified
+        let response = revalidate(product(), &request("if-modified-since", &http_date(&Utc::now()))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let dated = || warp::reply::with_header(warp::reply::json(&"mug"), "last-modified", http_date(&modified)).into_response();
+        let response = revalidate(dated(), &request("if-modified-since", &http_date(&Utc::now()))).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(!response.headers().contains_key("etag"));
+        let response = revalidate(dated(), &request("if-modified-since", &http_date(&(modified - Duration::hours(1))))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -28,7 +28,73 @@ pub struct Config {
     pub db_port: u16, //Database port, default: 5432
 
     #[clap(long, default_value = "data")]
-    pub db_name: String //Database name, default: "data"
+    pub db_name: String, //Database name, default: "data"
+
+    #[clap(long)]
+    pub smtp_host: Option<String>, //SMTP relay, mails are written to `mail_dir` when unset
+
+    #[clap(long, default_value = "587")]
+    pub smtp_port: u16, //SMTP port, default: 587
+
+    #[clap(long)]
+    pub smtp_username: Option<String>, //SMTP username, default: none
+
+    #[clap(long)]
+    pub smtp_password: Option<String>, //SMTP password, default: none
+
+    #[clap(long, default_value = "no-reply@localhost")]
+    pub mail_from: String, //Sender of the mails, default: "no-reply@localhost"
+
+    #[clap(long, default_value = "mail")]
+    pub mail_dir: String, //Directory of the file mailer, default: "mail"
+
+    #[clap(long, default_value = "http://localhost:3030")]
+    pub app_url: String, //Base of the links sent by mail, default: "http://localhost:3030"
+
+    #[clap(long, default_value = "*")]
+    pub cors_allowed_origins: String, //Comma separated origins, `*` or `https://*.example.com` patterns, default: "*"
+
+    #[clap(long, default_value = "authorization,content-type,if-match,idempotency-key")]
+    pub cors_allowed_headers: String, //Comma separated request headers allowed cross-origin
+
+    #[clap(long, default_value = "GET,POST,PUT,PATCH,DELETE,OPTIONS")]
+    pub cors_allowed_methods: String, //Comma separated methods allowed cross-origin
+
+    #[clap(long, default_value = "etag,idempotent-replayed,content-disposition,deprecation,sunset,link")]
+    pub cors_exposed_headers: String, //Comma separated response headers readable cross-origin
+
+    #[clap(long)]
+    pub cors_allow_credentials: bool, //Allow cookies and credentials cross-origin, needs listed origins, default: false
+
+    #[clap(long, default_value = "3600")]
+    pub cors_max_age: u32, //Seconds browsers may cache a preflight, default: 3600
+
+    #[clap(long, default_value_t = true, action = clap::ArgAction::Set)]
+    pub api_docs: bool, //Serve the docs page at /docs, /openapi.json is always served, default: true
+
+    #[clap(long, default_value = "2024-06-11")]
+    pub legacy_routes_deprecated_on: String, //Day the unversioned paths were deprecated, sent in `Deprecation`, default: "2024-06-11"
+
+    #[clap(long, default_value = "2027-06-30")]
+    pub legacy_routes_sunset: String, //Day the unversioned paths stop being served, sent in `Sunset`, must be in the future, default: "2027-06-30"
+
+    #[clap(long, default_value_t = true, action = clap::ArgAction::Set)]
+    pub compression: bool, //Compress replies with gzip or brotli, as negotiated by Accept-Encoding, default: true
+
+    #[clap(long, default_value = "public, max-age=60")]
+    pub catalog_cache_control: String, //Cache-Control of the public catalog replies, default: "public, max-age=60"
+
+    #[clap(long)]
+    pub product_cache: bool, //Cache product lists and products in memory, default: false
+
+    #[clap(long, default_value = "30")]
+    pub product_cache_ttl: u64, //Seconds a cached product or list is served, default: 30
+
+    #[clap(long, default_value = "1000")]
+    pub product_cache_capacity: usize, //Lists, and as many products, kept at most, default: 1000
+
+    #[clap(long, default_value = "256")]
+    pub event_buffer: usize //Product events kept for slow stream subscribers, default: 256
 }
 
 impl Config {
@@ -43,7 +109,7 @@ impl Config {
         //     panic!("Bad word api key not set");
         // }
 
-        if let Err(_) = env::var("PASETO_KEY") {
+        if env::var("PASETO_KEY").is_err() {
             panic!("Paseto key not set");
         }
 
@@ -52,13 +118,45 @@ impl Config {
             .ok()
             .map(|val| val.parse::<u16>())
             .unwrap_or(Ok(config.port))
-            .map_err(|e| handle_errors::Error::ParseError(e))?;
+            .map_err(handle_errors::Error::ParseError)?;
 
         let db_user = env::var("DB_USER").unwrap_or_else(|_| config.db_user.to_owned());
         let db_password = env::var("DB_PASSWORD").unwrap();
         let db_host = env::var("DB_HOST").unwrap_or_else(|_| config.db_host.to_owned());
         let db_port = env::var("DB_PORT").unwrap_or_else(|_| config.db_port.to_string());
         let db_name = env::var("DB_NAME").unwrap_or_else(|_| config.db_name.to_owned());
+        let smtp_host = env::var("SMTP_HOST").ok().or(config.smtp_host);
+        let smtp_port = env::var("SMTP_PORT").unwrap_or_else(|_| config.smtp_port.to_string());
+        let smtp_username = env::var("SMTP_USERNAME").ok().or(config.smtp_username);
+        let smtp_password = env::var("SMTP_PASSWORD").ok().or(config.smtp_password);
+        let mail_from = env::var("MAIL_FROM").unwrap_or(config.mail_from);
+        let mail_dir = env::var("MAIL_DIR").unwrap_or(config.mail_dir);
+        let app_url = env::var("APP_URL").unwrap_or(config.app_url);
+        let cors_allowed_origins = env::var("CORS_ALLOWED_ORIGINS").unwrap_or(config.cors_allowed_origins);
+        let cors_allowed_headers = env::var("CORS_ALLOWED_HEADERS").unwrap_or(config.cors_allowed_headers);
+        let cors_allowed_methods = env::var("CORS_ALLOWED_METHODS").unwrap_or(config.cors_allowed_methods);
+        let cors_exposed_headers = env::var("CORS_EXPOSED_HEADERS").unwrap_or(config.cors_exposed_headers);
+        let cors_allow_credentials = env::var("CORS_ALLOW_CREDENTIALS")
+            .map(|val| val == "true" || val == "1")
+            .unwrap_or(config.cors_allow_credentials);
+        let cors_max_age = env::var("CORS_MAX_AGE").unwrap_or_else(|_| config.cors_max_age.to_string());
+        let api_docs = env::var("API_DOCS")
+            .map(|val| val == "true" || val == "1")
+            .unwrap_or(config.api_docs);
+        let legacy_routes_deprecated_on =
+            env::var("LEGACY_ROUTES_DEPRECATED_ON").unwrap_or(config.legacy_routes_deprecated_on);
+        let legacy_routes_sunset = env::var("LEGACY_ROUTES_SUNSET").unwrap_or(config.legacy_routes_sunset);
+        let compression = env::var("COMPRESSION")
+            .map(|val| val == "true" || val == "1")
+            .unwrap_or(config.compression);
+        let catalog_cache_control = env::var("CATALOG_CACHE_CONTROL").unwrap_or(config.catalog_cache_control);
+        let product_cache = env::var("PRODUCT_CACHE")
+            .map(|val| val == "true" || val == "1")
+            .unwrap_or(config.product_cache);
+        let product_cache_ttl = env::var("PRODUCT_CACHE_TTL").unwrap_or_else(|_| config.product_cache_ttl.to_string());
+        let product_cache_capacity = env::var("PRODUCT_CACHE_CAPACITY")
+            .unwrap_or_else(|_| config.product_cache_capacity.to_string());
+        let event_buffer = env::var("EVENT_BUFFER").unwrap_or_else(|_| config.event_buffer.to_string());
 
         Ok(Config {
             log_level: config.log_level,
@@ -66,12 +164,41 @@ impl Config {
             db_user,
             db_password,
             db_host,
-            db_port: db_port.parse::<u16>().map_err(|e| handle_errors::Error::ParseError(e))?,
-            db_name
+            db_port: db_port.parse::<u16>().map_err(handle_errors::Error::ParseError)?,
+            db_name,
+            smtp_host,
+            smtp_port: smtp_port.parse::<u16>().map_err(handle_errors::Error::ParseError)?,
+            smtp_username,
+            smtp_password,
+            mail_from,
+            mail_dir,
+            app_url,
+            cors_allowed_origins,
+            cors_allowed_headers,
+            cors_allowed_methods,
+            cors_exposed_headers,
+            cors_allow_credentials,
+            cors_max_age: cors_max_age.parse::<u32>().map_err(handle_errors::Error::ParseError)?,
+            api_docs,
+            legacy_routes_deprecated_on,
+            legacy_routes_sunset,
+            compression,
+            catalog_cache_control,
+            product_cache,
+            product_cache_ttl: product_cache_ttl.parse::<u64>().map_err(handle_errors::Error::ParseError)?,
+            product_cache_capacity: product_cache_capacity.parse::<usize>().map_err(handle_errors::Error::ParseError)?,
+            event_buffer: event_buffer.parse::<usize>().map_err(handle_errors::Error::ParseError)?
         })
     }
 }
 
+/// The defaults of every option, as if the server was started without arguments nor environment
+impl Default for Config {
+    fn default() -> Self {
+        Config::parse_from([env!("CARGO_PKG_NAME")])
+    }
+}
+
 #[cfg(test)]
 mod config_test {
     use super::*;
@@ -79,6 +206,7 @@ mod config_test {
     fn set_env() {
         env::set_var("BAD_WORDS_API_KEY", "API_KEY");
         env::set_var("PASETO_KEY", "RANDOM WORDS WINTER MACINTOSH PC");
+        env::set_var("PORT", "8080");
         env::set_var("DB_USER", "user");
         env::set_var("DB_PASSWORD", "pass");
         env::set_var("DB_HOST", "localhost");
@@ -89,18 +217,15 @@ mod config_test {
 
     #[test]
     fn unset_and_set_api_kei() {
-        let result = std::panic::catch_unwind(|| Config::new());
+        let result = std::panic::catch_unwind(Config::new);
         assert!(result.is_err());
 
         set_env();
         let expected = Config {
-            log_level: "warn".to_string(),
             port: 8080,
             db_user: "user".to_string(),
             db_password: "pass".to_string(),
-            db_host: "localhost".to_string(),
-            db_port: 5432,
-            db_name: "data".to_string(),
+            ..Config::default()
         };
         let config = Config::new().unwrap();
         assert_eq!(config, expected);
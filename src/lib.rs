@@ -1,6 +1,6 @@
 #![warn(clippy::all)]
 
-use warp::{http::Method, Filter};
+use warp::{filters::BoxedFilter, Filter};
 use tokio::sync::{oneshot, oneshot::Sender};
 use tracing_subscriber::fmt::format::FmtSpan;
 
@@ -8,6 +8,12 @@ mod routes;
 mod store;
 pub mod types;
 pub mod config;
+pub mod compression;
+pub mod conditional;
+pub mod cors;
+pub mod mail;
+pub mod openapi;
+pub mod versioning;
 pub use handle_errors;
 
 pub struct OneshotHandler {
@@ -15,41 +21,188 @@ pub struct OneshotHandler {
 }
 
 /*
-@desc Function to build the main API routes
+@desc Function to build the main API routes. Every version is mounted under its prefix,
+the unversioned paths are deprecated aliases of /v1
  */
-async fn build_routes(store: store::Store) -> impl Filter<Extract = impl warp::Reply> + Clone {
+async fn build_routes(
+    store: store::Store,
+    mailer: mail::AccountMailer,
+    cors: cors::CorsPolicy,
+    legacy: versioning::LegacyRoutes,
+    caching: conditional::CatalogCaching,
+    compression: bool,
+    api_docs: bool
+) -> impl Filter<Extract = impl warp::Reply> + Clone {
+    let origin_guard = cors.origin_guard();
+    let cors = cors.builder();
+
+    //A /v2 gets its own routes_v2, built from the shared handlers, and is mounted alongside
+    let v1 = routes_v1(store, mailer, caching, api_docs);
+    let routes = versioning::ApiVersion::V1
+        .mount(v1.clone())
+        .or(legacy.alias(v1))
+        .with(cors);
+
+    let routes = origin_guard
+        .and(routes)
+        .with(warp::trace::request())
+        .recover(handle_errors::return_error);
+
+    compression::negotiated(compression, routes)
+}
+
+/// Every route of `routes_v1` as `(method, path)`, paths written as in the OpenAPI document.
+/// A route added to `routes_v1` belongs here too, the tests check both the filters and the spec against it
+#[cfg(test)]
+pub(crate) const ROUTES: &[(&str, &str)] = &[
+    ("POST", "/registration"),
+    ("POST", "/login"),
+    ("POST", "/login/2fa"),
+    ("POST", "/email/verify"),
+    ("POST", "/email/verify/resend"),
+    ("POST", "/password/forgot"),
+    ("POST", "/password/reset"),
+    ("POST", "/account/2fa/enroll"),
+    ("POST", "/account/2fa/confirm"),
+    ("POST", "/account/2fa/disable"),
+    ("GET", "/account/api-keys"),
+    ("POST", "/account/api-keys"),
+    ("DELETE", "/account/api-keys/{id}"),
+    ("GET", "/products"),
+    ("POST", "/products"),
+    ("GET", "/products/search"),
+    ("GET", "/products/stream"),
+    ("GET", "/products/stream/ws"),
+    ("POST", "/products/import"),
+    ("GET", "/products/export"),
+    ("GET", "/products/{id}"),
+    ("PUT", "/products/{id}"),
+    ("PATCH", "/products/{id}"),
+    ("DELETE", "/products/{id}"),
+    ("POST", "/products/{id}/restore"),
+    ("PUT", "/products/{id}/category"),
+    ("PUT", "/products/{id}/tags"),
+    ("GET", "/products/{id}/reviews"),
+    ("POST", "/products/{id}/reviews"),
+    ("PUT", "/reviews/{id}"),
+    ("DELETE", "/reviews/{id}"),
+    ("GET", "/categories"),
+    ("POST", "/categories"),
+    ("PUT", "/categories/{id}"),
+    ("DELETE", "/categories/{id}"),
+    ("GET", "/sellers/{id}"),
+    ("GET", "/sellers/{id}/products"),
+    ("PATCH", "/sellers/me"),
+    ("GET", "/cart"),
+    ("PUT", "/cart/items/{product_id}"),
+    ("DELETE", "/cart/items/{product_id}"),
+    ("POST", "/cart/checkout"),
+    ("GET", "/admin/audit"),
+    ("GET", "/admin/cache"),
+    ("DELETE", "/admin/products/{id}"),
+    ("GET", "/openapi.json"),
+    ("GET", "/docs"),
+];
+
+/*
+@desc Routes of the first version of the API, relative to its /v1 prefix
+ */
+fn routes_v1(
+    store: store::Store,
+    mailer: mail::AccountMailer,
+    caching: conditional::CatalogCaching,
+    api_docs: bool
+) -> BoxedFilter<(impl warp::Reply,)> {
+    let auth = routes::authentication::auth(store.clone());
     let store_filter = warp::any().map(move || store.clone());
+    let mailer_filter = warp::any().map(move || mailer.clone());
 
-    let cors = warp::cors()
-        .allow_any_origin()
-        .allow_header("content-type")
-        .allow_methods(&[
-            Method::PUT,
-            Method::DELETE,
-            Method::GET,
-            Method::POST,
-        ]);
     //POST method
     let registration = warp::post()
         .and(warp::path("registration"))
         .and(warp::path::end())
         .and(store_filter.clone())
-        .and(warp::body::json())
+        .and(mailer_filter.clone())
+        .and(routes::validation::json_body())
         .and_then(routes::authentication::register);
 
     let login = warp::post()
         .and(warp::path("login"))
         .and(warp::path::end())
         .and(store_filter.clone())
-        .and(warp::body::json())
+        .and(routes::validation::json_body())
         .and_then(routes::authentication::login);
 
+    let login_two_factor = warp::post()
+        .and(warp::path("login"))
+        .and(warp::path("2fa"))
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and(routes::validation::json_body())
+        .and_then(routes::two_factor::login_two_factor);
+
+    let enroll_two_factor = warp::post()
+        .and(warp::path("account"))
+        .and(warp::path("2fa"))
+        .and(warp::path("enroll"))
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and_then(routes::two_factor::enroll_two_factor);
+
+    let confirm_two_factor = warp::post()
+        .and(warp::path("account"))
+        .and(warp::path("2fa"))
+        .and(warp::path("confirm"))
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and(routes::validation::json_body())
+        .and_then(routes::two_factor::confirm_two_factor);
+
+    let create_api_key = warp::post()
+        .and(warp::path("account"))
+        .and(warp::path("api-keys"))
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and(routes::validation::json_body())
+        .and_then(routes::api_keys::create_api_key);
+
+    let get_api_keys = warp::get()
+        .and(warp::path("account"))
+        .and(warp::path("api-keys"))
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and_then(routes::api_keys::get_api_keys);
+
+    let revoke_api_key = warp::delete()
+        .and(warp::path("account"))
+        .and(warp::path("api-keys"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and_then(routes::api_keys::revoke_api_key);
+
+    let disable_two_factor = warp::post()
+        .and(warp::path("account"))
+        .and(warp::path("2fa"))
+        .and(warp::path("disable"))
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and(routes::validation::json_body())
+        .and_then(routes::two_factor::disable_two_factor);
+
     let add_product = warp::post()
         .and(warp::path("products"))
         .and(warp::path::end())
-        .and(routes::authentication::auth())
+        .and(auth.clone())
+        .and(warp::header::optional::<String>(types::idempotency::IDEMPOTENCY_KEY_HEADER))
         .and(store_filter.clone())
-        .and(warp::body::json())
+        .and(routes::validation::json_body())
         .and_then(routes::products::add_product);
 
     //GET method
@@ -60,14 +213,74 @@ async fn build_routes(store: store::Store) -> impl Filter<Extract = impl warp::R
         .and(store_filter.clone())
         .and_then(routes::products::get_products);
 
-    //PUT method
+    let search_products = warp::get()
+        .and(warp::path("products"))
+        .and(warp::path("search"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::products::search_products);
+
+    //Live product changes, kept out of the cached catalog as the replies never end
+    let stream_products = warp::get()
+        .and(warp::path("products"))
+        .and(warp::path("stream"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::events::stream_products);
+
+    let product_socket = warp::get()
+        .and(warp::path("products"))
+        .and(warp::path("stream"))
+        .and(warp::path("ws"))
+        .and(warp::path::end())
+        .and(warp::ws())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::events::product_socket);
+
+    //Bulk catalog import and export, declared before the routes taking a product id
+    let import_products = warp::post()
+        .and(warp::path("products"))
+        .and(warp::path("import"))
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(warp::query())
+        .and(warp::header::optional::<String>("content-type"))
+        .and(warp::header::optional::<String>(types::idempotency::IDEMPOTENCY_KEY_HEADER))
+        .and(store_filter.clone())
+        .and(warp::body::content_length_limit(types::catalog::MAX_IMPORT_SIZE))
+        .and(warp::body::bytes())
+        .and_then(routes::catalog::import_products);
+
+    let export_products = warp::get()
+        .and(warp::path("products"))
+        .and(warp::path("export"))
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::catalog::export_products);
+
+    let get_product_by_id = warp::get()
+        .and(warp::path("products"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and_then(routes::products::get_product);
+
+    //PUT and PATCH methods, both are partial updates
     let update_product = warp::put()
+        .or(warp::patch())
+        .unify()
         .and(warp::path("products"))
         .and(warp::path::param::<i32>())
         .and(warp::path::end())
-        .and(routes::authentication::auth())
+        .and(auth.clone())
+        .and(warp::header::optional::<String>("if-match"))
         .and(store_filter.clone())
-        .and(warp::body::json())
+        .and(routes::validation::json_body())
         .and_then(routes::products::update_product);
 
     //DELETE method
@@ -75,20 +288,327 @@ async fn build_routes(store: store::Store) -> impl Filter<Extract = impl warp::R
         .and(warp::path("products"))
         .and(warp::path::param::<i32>())
         .and(warp::path::end())
-        .and(routes::authentication::auth())
+        .and(auth.clone())
         .and(store_filter.clone())
         .and_then(routes::products::delete_product);
 
+    let restore_product = warp::post()
+        .and(warp::path("products"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("restore"))
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(warp::header::optional::<String>(types::idempotency::IDEMPOTENCY_KEY_HEADER))
+        .and(store_filter.clone())
+        .and_then(routes::products::restore_product);
+
+    let set_product_category = warp::put()
+        .and(warp::path("products"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("category"))
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and(routes::validation::json_body())
+        .and_then(routes::products::set_product_category);
+
+    let set_product_tags = warp::put()
+        .and(warp::path("products"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("tags"))
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and(routes::validation::json_body())
+        .and_then(routes::products::set_product_tags);
+
+    //Admin only
+    let purge_product = warp::delete()
+        .and(warp::path("admin"))
+        .and(warp::path("products"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and_then(routes::admin::purge_product);
+
+    let get_audit_events = warp::get()
+        .and(warp::path("admin"))
+        .and(warp::path("audit"))
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::admin::get_audit_events);
+
+    let get_cache_stats = warp::get()
+        .and(warp::path("admin"))
+        .and(warp::path("cache"))
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and_then(routes::admin::get_cache_stats);
+
+    //Category management
+    let get_categories = warp::get()
+        .and(warp::path("categories"))
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and_then(routes::categories::get_categories);
+
+    let add_category = warp::post()
+        .and(warp::path("categories"))
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(warp::header::optional::<String>(types::idempotency::IDEMPOTENCY_KEY_HEADER))
+        .and(store_filter.clone())
+        .and(routes::validation::json_body())
+        .and_then(routes::categories::add_category);
+
+    let update_category = warp::put()
+        .and(warp::path("categories"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and(routes::validation::json_body())
+        .and_then(routes::categories::update_category);
+
+    let delete_category = warp::delete()
+        .and(warp::path("categories"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and_then(routes::categories::delete_category);
+
+    //Shopping cart of the logged in account
+    let get_cart = warp::get()
+        .and(warp::path("cart"))
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and_then(routes::cart::get_cart);
+
+    let set_cart_item = warp::put()
+        .and(warp::path("cart"))
+        .and(warp::path("items"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and(routes::validation::json_body())
+        .and_then(routes::cart::set_cart_item);
+
+    let remove_cart_item = warp::delete()
+        .and(warp::path("cart"))
+        .and(warp::path("items"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and_then(routes::cart::remove_cart_item);
+
+    let checkout = warp::post()
+        .and(warp::path("cart"))
+        .and(warp::path("checkout"))
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(warp::header::optional::<String>(types::idempotency::IDEMPOTENCY_KEY_HEADER))
+        .and(store_filter.clone())
+        .and_then(routes::cart::checkout);
+
+    //Email verification and password reset
+    let verify_email = warp::post()
+        .and(warp::path("email"))
+        .and(warp::path("verify"))
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and(routes::validation::json_body())
+        .and_then(routes::accounts::verify_email);
+
+    let resend_verification = warp::post()
+        .and(warp::path("email"))
+        .and(warp::path("verify"))
+        .and(warp::path("resend"))
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and(mailer_filter.clone())
+        .and_then(routes::accounts::resend_verification);
+
+    let forgot_password = warp::post()
+        .and(warp::path("password"))
+        .and(warp::path("forgot"))
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and(mailer_filter.clone())
+        .and(routes::validation::json_body())
+        .and_then(routes::accounts::forgot_password);
+
+    let reset_password = warp::post()
+        .and(warp::path("password"))
+        .and(warp::path("reset"))
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and(routes::validation::json_body())
+        .and_then(routes::accounts::reset_password);
+
+    //Seller storefronts
+    let get_seller = warp::get()
+        .and(warp::path("sellers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(store_filter.clone())
+        .and_then(routes::sellers::get_seller);
+
+    let get_seller_products = warp::get()
+        .and(warp::path("sellers"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("products"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::sellers::get_seller_products);
+
+    let update_seller_profile = warp::patch()
+        .and(warp::path("sellers"))
+        .and(warp::path("me"))
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and(routes::validation::json_body())
+        .and_then(routes::sellers::update_seller_profile);
+
+    //Product reviews
+    let get_reviews = warp::get()
+        .and(warp::path("products"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("reviews"))
+        .and(warp::path::end())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::reviews::get_reviews);
 
-    registration
+    let add_review = warp::post()
+        .and(warp::path("products"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("reviews"))
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(warp::header::optional::<String>(types::idempotency::IDEMPOTENCY_KEY_HEADER))
+        .and(store_filter.clone())
+        .and(routes::validation::json_body())
+        .and_then(routes::reviews::add_review);
+
+    let update_review = warp::put()
+        .and(warp::path("reviews"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and(routes::validation::json_body())
+        .and_then(routes::reviews::update_review);
+
+    let delete_review = warp::delete()
+        .and(warp::path("reviews"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(auth.clone())
+        .and(store_filter.clone())
+        .and_then(routes::reviews::delete_review);
+
+    //OpenAPI document and its docs page
+    let openapi_json = warp::get()
+        .and(warp::path("openapi.json"))
+        .and(warp::path::end())
+        .and_then(routes::docs::openapi_json);
+
+    let docs_page = warp::get()
+        .and(warp::path("docs"))
+        .and(warp::path::end())
+        .and(warp::any().map(move || api_docs))
+        .and_then(routes::docs::docs_page);
+
+    //Each group is boxed to keep the nested filter types, and their futures, shallow
+    let account_routes = registration
         .or(login)
-        .or(get_product)
+        .or(login_two_factor)
+        .or(enroll_two_factor)
+        .or(confirm_two_factor)
+        .or(disable_two_factor)
+        .or(create_api_key)
+        .or(get_api_keys)
+        .or(revoke_api_key)
+        .or(verify_email)
+        .or(resend_verification)
+        .or(forgot_password)
+        .or(reset_password)
+        .or(update_seller_profile)
+        .boxed();
+
+    //Public catalog, cacheable by browsers and proxies and answered with 304 when unchanged
+    let catalog_routes = caching
+        .conditional(
+            get_product
+                .or(search_products)
+                .or(get_product_by_id)
+                .or(get_categories)
+                .or(get_seller)
+                .or(get_seller_products)
+                .or(get_reviews)
+        )
+        .boxed();
+
+    let product_routes = import_products
+        .or(export_products)
         .or(add_product)
         .or(update_product)
         .or(delete_product)
-        .with(cors)
-        .with(warp::trace::request())
-        .recover(handle_errors::return_error)
+        .or(restore_product)
+        .or(set_product_category)
+        .or(set_product_tags)
+        .boxed();
+
+    let event_routes = stream_products
+        .or(product_socket)
+        .boxed();
+
+    let category_routes = add_category
+        .or(update_category)
+        .or(delete_category)
+        .boxed();
+
+    let admin_routes = purge_product
+        .or(get_audit_events)
+        .or(get_cache_stats)
+        .boxed();
+
+    let cart_routes = get_cart
+        .or(set_cart_item)
+        .or(remove_cart_item)
+        .or(checkout)
+        .boxed();
+
+    let review_routes = add_review
+        .or(update_review)
+        .or(delete_review)
+        .boxed();
+
+    let docs_routes = openapi_json
+        .or(docs_page)
+        .boxed();
+
+    account_routes
+        .or(catalog_routes)
+        .or(product_routes)
+        .or(event_routes)
+        .or(category_routes)
+        .or(admin_routes)
+        .or(cart_routes)
+        .or(review_routes)
+        .or(docs_routes)
+        .boxed()
 }
 
 
@@ -100,12 +620,14 @@ pub async fn setup_store(config: &config::Config) -> Result<store::Store, handle
         "postgres://{}:{}@{}:{}/{}",
         config.db_user, config.db_password, config.db_host, config.db_port, config.db_name))
         .await
-        .map_err(|e| handle_errors::Error::DatabaseQueryError(e))?;
+        .map_err(handle_errors::Error::DatabaseQueryError)?
+        .with_product_cache(store::ProductCache::from_config(config))
+        .with_product_events(store::ProductEvents::from_config(config));
 
     let _ = sqlx::migrate!()
         .run(&store.clone().connection)
         .await
-        .map_err(|e| handle_errors::Error::MigrationError(e));
+        .map_err(handle_errors::Error::MigrationError);
 
     let log_filter = format!(
         "handle_errors={}, restful-api={}, warp={}",
@@ -125,15 +647,25 @@ pub async fn setup_store(config: &config::Config) -> Result<store::Store, handle
 @desc Entry point for running the API server.
  */
 pub async fn run(config: config::Config, store: store::Store) {
-    let routes = build_routes(store).await;
+    let mailer = mail::AccountMailer::from_config(&config).expect("Mailer can't be set");
+    let cors = cors::CorsPolicy::from_config(&config).expect("CORS can't be set");
+    let legacy = versioning::LegacyRoutes::from_config(&config).expect("Legacy routes can't be set");
+    let caching = conditional::CatalogCaching::from_config(&config).expect("Catalog caching can't be set");
+    tokio::spawn(store.clone().listen_for_changes());
+    let routes = build_routes(store, mailer, cors, legacy, caching, config.compression, config.api_docs).await;
     warp::serve(routes).run(([127, 0, 0, 1], config.port)).await;
 }
 
 /*
 @desc Function to create a one-shot API server.
  */
-pub async fn oneshot(store: store::Store) -> OneshotHandler {
-    let routes = build_routes(store).await;
+pub async fn oneshot(config: &config::Config, store: store::Store) -> OneshotHandler {
+    let mailer = mail::AccountMailer::from_config(config).expect("Mailer can't be set");
+    let cors = cors::CorsPolicy::from_config(config).expect("CORS can't be set");
+    let legacy = versioning::LegacyRoutes::from_config(config).expect("Legacy routes can't be set");
+    let caching = conditional::CatalogCaching::from_config(config).expect("Catalog caching can't be set");
+    tokio::spawn(store.clone().listen_for_changes());
+    let routes = build_routes(store, mailer, cors, legacy, caching, config.compression, config.api_docs).await;
     let (tx, rx) = oneshot::channel::<i32>();
 
     let socket: std::net::SocketAddr = "127.0.0.1:3030"
@@ -149,4 +681,56 @@ pub async fn oneshot(store: store::Store) -> OneshotHandler {
     OneshotHandler {
         sender: tx
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod routes_test {
+    use super::{config::Config, routes_v1, store::Store, ROUTES};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn every_route_is_served() {
+        let config = Config::default();
+        //Handlers reaching the database fail fast instead of waiting on a connection
+        let store = Store {
+            connection: sqlx::postgres::PgPoolOptions::new()
+                .acquire_timeout(Duration::from_millis(50))
+                .connect_lazy("postgres://localhost:1/unused")
+                .unwrap(),
+            product_cache: crate::store::ProductCache::disabled(),
+            product_events: crate::store::ProductEvents::new(1),
+            changes: crate::store::ChangeFeed::new(),
+        };
+        let routes = routes_v1(
+            store,
+            crate::mail::AccountMailer::from_config(&config).unwrap(),
+            crate::conditional::CatalogCaching::from_config(&config).unwrap(),
+            true,
+        );
+
+        for (method, path) in ROUTES {
+            let path: String = path
+                .split('/')
+                .map(|segment| if segment.starts_with('{') { "1" } else { segment })
+                .collect::<Vec<_>>()
+                .join("/");
+            //Unmatched paths reject with the same causes whatever the path, so any
+            //difference from an unknown path means a filter past the path matched
+            let unknown = warp::test::request()
+                .method(method)
+                .path("/__unknown__")
+                .filter(&routes)
+                .await
+                .err()
+                .map(|rejection| format!("{:?}", rejection));
+            let served = warp::test::request()
+                .method(method)
+                .path(&path)
+                .filter(&routes)
+                .await
+                .err()
+                .map(|rejection| format!("{:?}", rejection));
+            assert_ne!(served, unknown, "{} {} isn't served", method, path);
+        }
+    }
+}
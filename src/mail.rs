@@ -0,0 +1,202 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::prelude::*;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+
+use handle_errors::Error;
+
+use crate::config::Config;
+use crate::types::account_tokens::TokenPurpose;
+
+/// A plain text mail to a single recipient
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers mails, the implementation is picked from the configuration at startup
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: Mail) -> Result<(), Error>;
+}
+
+/// Writes the mails of the account flows, links point at the front-end served at `app_url`
+#[derive(Clone)]
+pub struct AccountMailer {
+    mailer: Arc<dyn Mailer>,
+    app_url: String,
+}
+
+impl AccountMailer {
+    pub fn new(mailer: Arc<dyn Mailer>, app_url: &str) -> Self {
+        AccountMailer {
+            mailer,
+            app_url: app_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /*
+    @desc Build the mailer described by the configuration: SMTP when a host is set,
+    files in `mail_dir` otherwise
+     */
+    pub fn from_config(config: &Config) -> Result<Self, Error> {
+        let mailer: Arc<dyn Mailer> = match &config.smtp_host {
+            Some(host) => Arc::new(SmtpMailer::new(config, host)?),
+            None => Arc::new(FileMailer::new(&config.mail_dir, &config.mail_from)),
+        };
+        Ok(AccountMailer::new(mailer, &config.app_url))
+    }
+
+    pub async fn send_verification(&self, to: &str, token: &str) -> Result<(), Error> {
+        self.mailer.send(Mail {
+            to: to.to_string(),
+            subject: "Verify your email address".to_string(),
+            body: format!(
+                "Confirm your email address to start selling:\n{}/verify-email?token={}\n\n\
+                Token: {}\n\nThe link expires in {} hours.",
+                self.app_url, token, token, TokenPurpose::VerifyEmail.ttl_minutes() / 60
+            ),
+        }).await
+    }
+
+    pub async fn send_password_reset(&self, to: &str, token: &str) -> Result<(), Error> {
+        self.mailer.send(Mail {
+            to: to.to_string(),
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Pick a new password:\n{}/reset-password?token={}\n\n\
+                Token: {}\n\nThe link expires in {} minutes. \
+                Ignore this mail if you didn't ask for a new password.",
+                self.app_url, token, token, TokenPurpose::ResetPassword.ttl_minutes()
+            ),
+        }).await
+    }
+}
+
+/// Relays mails through an SMTP server over STARTTLS
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(config: &Config, host: &str) -> Result<Self, Error> {
+        let mut transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+            .map_err(|e| Error::MailError(e.to_string()))?
+            .port(config.smtp_port);
+        if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password) {
+            transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(SmtpMailer {
+            transport: transport.build(),
+            from: parse_mailbox(&config.mail_from)?,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, mail: Mail) -> Result<(), Error> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(parse_mailbox(&mail.to)?)
+            .subject(mail.subject)
+            .body(mail.body)
+            .map_err(|e| Error::MailError(e.to_string()))?;
+
+        self.transport
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|e| Error::MailError(e.to_string()))
+    }
+}
+
+/// Writes every mail to its own file and logs it, meant for local development and tests
+pub struct FileMailer {
+    dir: PathBuf,
+    from: String,
+}
+
+impl FileMailer {
+    pub fn new(dir: &str, from: &str) -> Self {
+        FileMailer {
+            dir: PathBuf::from(dir),
+            from: from.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: Mail) -> Result<(), Error> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| Error::MailError(e.to_string()))?;
+
+        let path = self.dir.join(mail_file_name(&mail.to, Utc::now()));
+        let content = format!(
+            "From: {}\nTo: {}\nSubject: {}\n\n{}\n",
+            self.from, mail.to, mail.subject, mail.body
+        );
+        tokio::fs::write(&path, content)
+            .await
+            .map_err(|e| Error::MailError(e.to_string()))?;
+
+        tracing::event!(tracing::Level::INFO, to = %mail.to, subject = %mail.subject, path = %path.display(), "mail written");
+        Ok(())
+    }
+}
+
+/// Mails of a recipient sort by the time they were sent
+fn mail_file_name(to: &str, sent_on: DateTime<Utc>) -> String {
+    let recipient: String = to
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '@' || c == '.' { c } else { '_' })
+        .collect();
+    format!("{}-{}.eml", sent_on.format("%Y%m%dT%H%M%S%.6f"), recipient)
+}
+
+fn parse_mailbox(address: &str) -> Result<Mailbox, Error> {
+    address
+        .parse()
+        .map_err(|e: lettre::address::AddressError| Error::MailError(e.to_string()))
+}
+
+#[cfg(test)]
+mod mail_test {
+    use super::{mail_file_name, FileMailer, Mail, Mailer, Utc};
+
+    #[test]
+    fn file_name_is_safe() {
+        let name = mail_file_name("../evil/user@example.com", Utc::now());
+        assert!(name.ends_with("-.._evil_user@example.com.eml"));
+        assert!(!name.contains('/'));
+    }
+
+    #[tokio::test]
+    async fn file_mailer_writes_mail() {
+        let dir = std::env::temp_dir().join(format!("mail-test-{}", std::process::id()));
+        let mailer = FileMailer::new(dir.to_str().unwrap(), "no-reply@localhost");
+        mailer.send(Mail {
+            to: "user@example.com".to_string(),
+            subject: "Hello".to_string(),
+            body: "token: abc".to_string(),
+        }).await.unwrap();
+
+        let mut entries = std::fs::read_dir(&dir).unwrap();
+        let path = entries.next().unwrap().unwrap().path();
+        let content = std::fs::read_to_string(path).unwrap();
+        assert!(content.contains("To: user@example.com"));
+        assert!(content.contains("token: abc"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
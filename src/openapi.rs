@@ -0,0 +1,411 @@
+use serde::Serialize;
+use utoipa::openapi::path::PathItemType;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{ContentBuilder, ObjectBuilder, Ref, ResponseBuilder, SchemaType};
+use utoipa::{IntoParams, Modify, OpenApi, ToSchema};
+
+use handle_errors::FieldError;
+
+use crate::routes;
+use crate::types::{
+    account_tokens::{ForgotPassword, ResetPassword, VerifyEmail},
+    accounts::{AccessToken, Account, AccountId, Credentials, NewAccount},
+    api_keys::{ApiKey as AccountApiKey, ApiKeyId, ApiScope, CreatedApiKey, NewApiKey},
+    audit::AuditEvent,
+    cache::CacheStats,
+    cart::{Cart, CartItem, CartQuantity},
+    catalog::{ImportReport, ImportRowError},
+    events::{ProductEvent, ProductEventKind},
+    categories::{Category, CategoryId, NewCategory},
+    money::{Money, MoneyUpdate},
+    products::{NewProducts, ProductCategory, ProductId, ProductTags, Products, UpdateProduct},
+    reviews::{NewReview, Review, ReviewId},
+    search::SearchResult,
+    sellers::{SellerProfile, UpdateSellerProfile},
+    two_factor::{BackupCodes, DisableTwoFactor, TotpCode, TotpEnrollment, TwoFactorChallenge, TwoFactorLogin},
+};
+
+/// OpenAPI 3 document of the API, generated from the handlers and the types they exchange
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Marketplace API",
+        description = "Products, categories, carts and reviews of the marketplace. \
+            The unversioned paths still answer as deprecated aliases of /v1"
+    ),
+    servers((url = "/v1", description = "Version 1")),
+    paths(
+        routes::authentication::register,
+        routes::authentication::login,
+        routes::two_factor::login_two_factor,
+        routes::two_factor::enroll_two_factor,
+        routes::two_factor::confirm_two_factor,
+        routes::two_factor::disable_two_factor,
+        routes::api_keys::create_api_key,
+        routes::api_keys::get_api_keys,
+        routes::api_keys::revoke_api_key,
+        routes::accounts::verify_email,
+        routes::accounts::resend_verification,
+        routes::accounts::forgot_password,
+        routes::accounts::reset_password,
+        routes::sellers::get_seller,
+        routes::sellers::get_seller_products,
+        routes::sellers::update_seller_profile,
+        routes::products::get_products,
+        routes::products::search_products,
+        routes::catalog::import_products,
+        routes::catalog::export_products,
+        routes::events::stream_products,
+        routes::events::product_socket,
+        routes::products::get_product,
+        routes::products::add_product,
+        routes::products::update_product,
+        routes::products::delete_product,
+        routes::products::restore_product,
+        routes::products::set_product_category,
+        routes::products::set_product_tags,
+        routes::categories::get_categories,
+        routes::categories::add_category,
+        routes::categories::update_category,
+        routes::categories::delete_category,
+        routes::admin::purge_product,
+        routes::admin::get_audit_events,
+        routes::admin::get_cache_stats,
+        routes::cart::get_cart,
+        routes::cart::set_cart_item,
+        routes::cart::remove_cart_item,
+        routes::cart::checkout,
+        routes::reviews::get_reviews,
+        routes::reviews::add_review,
+        routes::reviews::update_review,
+        routes::reviews::delete_review,
+        routes::docs::openapi_json,
+        routes::docs::docs_page,
+    ),
+    components(schemas(
+        ValidationErrors, FieldError,
+        AccessToken, Account, AccountId, Credentials, NewAccount,
+        VerifyEmail, ForgotPassword, ResetPassword,
+        TotpCode, TwoFactorLogin, DisableTwoFactor, TotpEnrollment, BackupCodes, TwoFactorChallenge,
+        AccountApiKey, ApiKeyId, ApiScope, CreatedApiKey, NewApiKey,
+        SellerProfile, UpdateSellerProfile,
+        Products, ProductId, NewProducts, UpdateProduct, ProductCategory, ProductTags, Money, MoneyUpdate, SearchResult,
+        ImportReport, ImportRowError,
+        ProductEvent, ProductEventKind,
+        Category, CategoryId, NewCategory,
+        AuditEvent, CacheStats,
+        Cart, CartItem, CartQuantity,
+        Review, ReviewId, NewReview,
+    )),
+    modifiers(&SecuritySchemes, &ErrorResponses, &MethodAliases),
+    tags(
+        (name = "accounts", description = "Registration, login, two-factor authentication and API keys"),
+        (name = "products", description = "Catalog of the sellers"),
+        (name = "events", description = "Live product changes over Server-Sent Events or a WebSocket"),
+        (name = "categories", description = "Category tree of the catalog"),
+        (name = "cart", description = "Shopping cart of the logged in account"),
+        (name = "reviews", description = "Product reviews"),
+        (name = "sellers", description = "Seller storefronts"),
+        (name = "admin", description = "Moderation, admin accounts only"),
+        (name = "docs", description = "This document"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Body of a `400 Bad Request`, sent when a request body or query parameter fails validation
+#[derive(Serialize, ToSchema)]
+pub struct ValidationErrors {
+    #[schema(example = "Request body failed validation")]
+    pub message: String,
+    pub errors: Vec<FieldError>,
+}
+
+/// Query parameters of the paginated lists, both are needed to paginate
+#[derive(IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PaginationQuery {
+    /// Number of items returned
+    pub limit: Option<i32>,
+    /// Number of items skipped
+    pub offset: Option<i32>,
+}
+
+/// Query parameters of `GET /products`
+#[derive(IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProductQuery {
+    /// Number of products returned, along `offset`
+    pub limit: Option<i32>,
+    /// Number of products skipped, along `limit`
+    pub offset: Option<i32>,
+    /// Id of a category, its subcategories included
+    pub category: Option<i32>,
+    /// Tag the products carry, case insensitive
+    pub tag: Option<String>,
+    /// Id of the seller account
+    pub seller: Option<i32>,
+    /// `id`, oldest first, or `rating`, best rated first
+    pub sort: Option<String>,
+}
+
+/// Query parameters of `GET /sellers/{id}/products`
+#[derive(IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SellerProductQuery {
+    /// Number of products returned, along `offset`
+    pub limit: Option<i32>,
+    /// Number of products skipped, along `limit`
+    pub offset: Option<i32>,
+    /// Id of a category, its subcategories included
+    pub category: Option<i32>,
+    /// Tag the products carry, case insensitive
+    pub tag: Option<String>,
+    /// `id`, oldest first, or `rating`, best rated first
+    pub sort: Option<String>,
+}
+
+/// Query parameters of `GET /products/search`
+#[derive(IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchQuery {
+    /// Search terms, `"exact phrase"`, `or` and `-excluded` are understood
+    pub q: String,
+    /// Number of results returned, along `offset`
+    pub limit: Option<i32>,
+    /// Number of results skipped, along `limit`
+    pub offset: Option<i32>,
+}
+
+/// Query parameters of `GET /admin/audit`
+#[derive(IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    /// Id of the account behind the events
+    pub actor: Option<i32>,
+    /// Action of the events, like `product.update`
+    pub action: Option<String>,
+    /// Kind of target, like `product`
+    pub target_type: Option<String>,
+    /// Id of the target
+    pub target_id: Option<i32>,
+    /// Number of events returned, along `offset`
+    pub limit: Option<i32>,
+    /// Number of events skipped, along `limit`
+    pub offset: Option<i32>,
+}
+
+/// Query parameters of `POST /products/import`
+#[derive(IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportQuery {
+    /// `csv` or `ndjson`, taken from the content type when missing
+    pub format: Option<String>,
+    /// Check the rows without importing them
+    pub dry_run: Option<bool>,
+}
+
+/// Query parameters of `GET /products/export`
+#[derive(IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery {
+    /// `csv`, the default, or `ndjson`
+    pub format: Option<String>,
+}
+
+/// Query parameters of the product change streams, every change when both are missing
+#[derive(IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EventQuery {
+    /// Id of the seller account whose products are followed
+    pub seller: Option<i32>,
+    /// Id of the followed product
+    pub product: Option<i32>,
+}
+
+/// Both ways of authenticating, a session token from `POST /login` or an API key
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("PASETO")
+                    .description(Some("Session token returned by `POST /login`"))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                "Authorization",
+                "`ApiKey <key>`, with a key created at `POST /account/api-keys`",
+            ))),
+        );
+    }
+}
+
+/// Errors shared by the operations, added once as components rather than on every handler
+struct ErrorResponses;
+
+impl Modify for ErrorResponses {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let text = |description: &str| {
+            ResponseBuilder::new()
+                .description(description)
+                .content("text/plain", ContentBuilder::new().schema(ObjectBuilder::new().schema_type(SchemaType::String)).build())
+                .build()
+        };
+
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.responses.insert(
+            "ValidationError".to_string(),
+            ResponseBuilder::new()
+                .description("A field fails validation, every broken rule is listed")
+                .content(
+                    "application/json",
+                    ContentBuilder::new().schema(Ref::from_schema_name("ValidationErrors")).build(),
+                )
+                .build()
+                .into(),
+        );
+        components.responses.insert(
+            "Unauthenticated".to_string(),
+            text("The `Authorization` header is missing, malformed or expired").into(),
+        );
+        components.responses.insert(
+            "Forbidden".to_string(),
+            text("The API key lacks the scope of the request method").into(),
+        );
+        components.responses.insert("NotFound".to_string(), text("Nothing has this id").into());
+        components.responses.insert(
+            "Unprocessable".to_string(),
+            text("The request is well formed but can't be carried out, the reason is in the body").into(),
+        );
+
+        components.responses.insert(
+            "NotModified".to_string(),
+            ResponseBuilder::new()
+                .description("The `ETag` sent in `If-None-Match`, or the date in `If-Modified-Since`, is current")
+                .build()
+                .into(),
+        );
+
+        for (path, item) in openapi.paths.paths.iter_mut() {
+            for (method, operation) in item.operations.iter_mut() {
+                let public = operation.security.as_ref().map_or(true, |security| security.is_empty());
+                let catalog = !operation
+                    .tags
+                    .as_ref()
+                    .is_some_and(|tags| tags.iter().any(|tag| tag == "docs" || tag == "events"));
+                let responses = &mut operation.responses.responses;
+                let mut add = |status: &str, name: &str| {
+                    responses
+                        .entry(status.to_string())
+                        .or_insert_with(|| Ref::from_response_name(name).into());
+                };
+                if operation.request_body.is_some() {
+                    add("400", "ValidationError");
+                }
+                if operation.security.as_ref().is_some_and(|security| !security.is_empty()) {
+                    add("401", "Unauthenticated");
+                }
+                //Requirements only serialize their schemes, `{"api_key": []}`
+                let api_key = operation.security.iter().flatten().any(|requirement| {
+                    serde_json::to_value(requirement).is_ok_and(|value| value.get("api_key").is_some())
+                });
+                if api_key {
+                    add("403", "Forbidden");
+                }
+                if path.contains('{') {
+                    add("404", "NotFound");
+                }
+                if *method == PathItemType::Get && public && catalog {
+                    add("304", "NotModified");
+                }
+                add("422", "Unprocessable");
+            }
+        }
+    }
+}
+
+/// `PATCH /products/{id}` is served by the handler of `PUT /products/{id}`, which
+/// can only document one method
+struct MethodAliases;
+
+impl Modify for MethodAliases {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(item) = openapi.paths.paths.get_mut("/products/{id}") {
+            if let Some(mut operation) = item.operations.get(&PathItemType::Put).cloned() {
+                operation.operation_id = operation.operation_id.map(|id| format!("{}_patch", id));
+                item.operations.insert(PathItemType::Patch, operation);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod openapi_test {
+    use super::{ApiDoc, OpenApi, PathItemType};
+    use crate::ROUTES;
+    use std::collections::BTreeSet;
+
+    fn method(item: &PathItemType) -> &'static str {
+        match item {
+            PathItemType::Get => "get",
+            PathItemType::Post => "post",
+            PathItemType::Put => "put",
+            PathItemType::Delete => "delete",
+            PathItemType::Options => "options",
+            PathItemType::Head => "head",
+            PathItemType::Patch => "patch",
+            PathItemType::Trace => "trace",
+            PathItemType::Connect => "connect",
+        }
+    }
+
+    fn documented_routes() -> BTreeSet<(String, String)> {
+        let spec = ApiDoc::openapi();
+        let mut routes = BTreeSet::new();
+        for (path, item) in spec.paths.paths.iter() {
+            for operation in item.operations.keys() {
+                routes.insert((method(operation).to_string(), path.clone()));
+            }
+        }
+        routes
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        let declared: BTreeSet<(String, String)> = ROUTES
+            .iter()
+            .map(|(method, path)| (method.to_lowercase(), path.to_string()))
+            .collect();
+        let documented = documented_routes();
+
+        let missing: Vec<_> = declared.difference(&documented).collect();
+        assert!(missing.is_empty(), "routes without a spec entry: {:?}", missing);
+        let stale: Vec<_> = documented.difference(&declared).collect();
+        assert!(stale.is_empty(), "spec entries without a route: {:?}", stale);
+    }
+
+    #[test]
+    fn shared_errors_and_security() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let add_product = &spec["paths"]["/products"]["post"];
+        assert_eq!(add_product["responses"]["400"]["$ref"], "#/components/responses/ValidationError");
+        assert_eq!(add_product["responses"]["401"]["$ref"], "#/components/responses/Unauthenticated");
+        assert!(spec["paths"]["/products"]["get"]["responses"].get("401").is_none());
+        assert_eq!(
+            spec["paths"]["/products/{id}"]["put"]["responses"]["403"]["$ref"],
+            "#/components/responses/Forbidden"
+        );
+        assert!(spec["paths"]["/account/2fa/enroll"]["post"]["responses"].get("403").is_none());
+        assert_eq!(spec["paths"]["/products"]["get"]["responses"]["304"]["$ref"], "#/components/responses/NotModified");
+        assert!(spec["components"]["securitySchemes"]["bearer"].is_object());
+        assert!(spec["components"]["schemas"]["Products"].is_object());
+    }
+}
@@ -0,0 +1,99 @@
+use warp::http::StatusCode;
+
+use crate::mail::AccountMailer;
+use crate::routes::authentication::hash_password;
+use crate::store::Store;
+use crate::types::account_tokens::{ForgotPassword, ResetPassword, TokenPurpose, VerifyEmail};
+use crate::types::accounts::Session;
+
+/*
+@desc Verify the email address of an account with the token it received by mail
+@path POST /email/verify
+ */
+#[utoipa::path(post, path = "/email/verify", tag = "accounts", request_body = VerifyEmail, responses((status = 200, description = "Email address verified", body = String, content_type = "text/plain")))]
+pub async fn verify_email(
+    store: Store,
+    body: VerifyEmail
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.verify_email(body.token).await {
+        Ok(_) => Ok(warp::reply::with_status(
+            "Email address verified".to_string(),
+            StatusCode::OK
+        )),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
+
+/*
+@desc Send the verification mail again to the logged in account, earlier tokens stop working
+@path POST /email/verify/resend
+ */
+#[utoipa::path(post, path = "/email/verify/resend", tag = "accounts", security(("bearer" = []), ("api_key" = [])), responses((status = 200, description = "Verification mail sent, or address already verified", body = String, content_type = "text/plain")))]
+pub async fn resend_verification(
+    session: Session,
+    store: Store,
+    mailer: AccountMailer
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account = store.get_account_by_id(&session.account_id).await?;
+    let email = match (&account.email, account.email_verified_on) {
+        (Some(email), None) => email,
+        _ => return Ok(warp::reply::with_status(
+            "Email address already verified".to_string(),
+            StatusCode::OK
+        )),
+    };
+
+    let token = store.create_account_token(&account.id, TokenPurpose::VerifyEmail).await?;
+    match mailer.send_verification(email, &token).await {
+        Ok(_) => Ok(warp::reply::with_status(
+            "Verification mail sent".to_string(),
+            StatusCode::OK
+        )),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
+
+/*
+@desc Send a password reset mail. The reply is the same whether the address is registered or not,
+so that it can't be used to find out which addresses have an account
+@path POST /password/forgot
+ */
+#[utoipa::path(post, path = "/password/forgot", tag = "accounts", request_body = ForgotPassword, responses((status = 200, description = "Same reply whether the address is registered or not", body = String, content_type = "text/plain")))]
+pub async fn forgot_password(
+    store: Store,
+    mailer: AccountMailer,
+    body: ForgotPassword
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if let Some(account) = store.get_account_by_email(&body.email).await? {
+        let token = store.create_account_token(&account.id, TokenPurpose::ResetPassword).await?;
+        if let Some(email) = &account.email {
+            if let Err(e) = mailer.send_password_reset(email, &token).await {
+                tracing::event!(tracing::Level::ERROR, "{}", e);
+            }
+        }
+    }
+
+    Ok(warp::reply::with_status(
+        "If an account uses this address, a reset link was sent to it".to_string(),
+        StatusCode::OK
+    ))
+}
+
+/*
+@desc Pick a new password with the token received by mail
+@path POST /password/reset
+ */
+#[utoipa::path(post, path = "/password/reset", tag = "accounts", request_body = ResetPassword, responses((status = 200, description = "Password changed", body = String, content_type = "text/plain")))]
+pub async fn reset_password(
+    store: Store,
+    body: ResetPassword
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let password_hash = hash_password(body.password.as_bytes());
+    match store.reset_password(body.token, password_hash).await {
+        Ok(_) => Ok(warp::reply::with_status(
+            "Password changed".to_string(),
+            StatusCode::OK
+        )),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+
+use warp::http::StatusCode;
+
+use crate::openapi::AuditQuery;
+use crate::store::Store;
+use crate::types::accounts::Session;
+use crate::types::audit::extract_audit_filter;
+use crate::types::events::ProductEvent;
+use crate::types::pagination::{extract_pagination, Pagination};
+
+/*
+@desc Remove a product for good, admin only
+@path DELETE /admin/products/{id}
+ */
+#[utoipa::path(delete, path = "/admin/products/{id}", tag = "admin", security(("bearer" = []), ("api_key" = [])), params(("id" = i32, Path, description = "Id of the product")), responses((status = 200, description = "Product purged", body = String, content_type = "text/plain")))]
+pub async fn purge_product(
+    id: i32,
+    session: Session,
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = session.account_id;
+    if store.is_admin(&account_id).await? {
+        match store.clone().purge_product(id, account_id).await {
+            Ok(purged) => {
+                store.product_events.publish(ProductEvent::deleted(id, purged.seller_id));
+                Ok(warp::reply::with_status(
+                    format!("Product {} purged", id),
+                    StatusCode::OK
+                ))
+            }
+            Err(e) => Err(warp::reject::custom(e))
+        }
+    } else {
+        Err(warp::reject::custom(handle_errors::Error::Unauthorized))
+    }
+}
+
+/*
+@desc Browse the audit log, newest events first, admin only
+@path GET /admin/audit?limit=&offset=&actor=&action=&target_type=&target_id=
+ */
+#[utoipa::path(get, path = "/admin/audit", tag = "admin", security(("bearer" = []), ("api_key" = [])), params(AuditQuery), responses((status = 200, body = Vec<AuditEvent>)))]
+pub async fn get_audit_events(
+    session: Session,
+    params: HashMap<String, String>,
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if !store.is_admin(&session.account_id).await? {
+        return Err(warp::reject::custom(handle_errors::Error::Unauthorized));
+    }
+
+    let filter = extract_audit_filter(&params)?;
+    let mut pagination = Pagination::default();
+    if params.contains_key("limit") || params.contains_key("offset") {
+        pagination = extract_pagination(params)?;
+    }
+
+    match store.get_audit_events(pagination.limit, pagination.offset, filter).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
+
+/*
+@desc Hit and miss counters of the product read cache, admin only
+@path GET /admin/cache
+ */
+#[utoipa::path(get, path = "/admin/cache", tag = "admin", security(("bearer" = []), ("api_key" = [])), responses((status = 200, body = CacheStats)))]
+pub async fn get_cache_stats(
+    session: Session,
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if !store.is_admin(&session.account_id).await? {
+        return Err(warp::reject::custom(handle_errors::Error::Unauthorized));
+    }
+
+    Ok(warp::reply::json(&store.product_cache.stats()))
+}
@@ -0,0 +1,60 @@
+use warp::http::StatusCode;
+
+use crate::routes::authentication::require_login;
+use crate::store::Store;
+use crate::types::accounts::Session;
+use crate::types::api_keys::NewApiKey;
+
+/*
+@desc Create an API key for the logged in account. The key is part of the reply and never shown again,
+it is sent as `Authorization: ApiKey <key>`
+@path POST /account/api-keys
+ */
+#[utoipa::path(post, path = "/account/api-keys", tag = "accounts", security(("bearer" = [])), request_body = NewApiKey, responses((status = 201, body = CreatedApiKey)))]
+pub async fn create_api_key(
+    session: Session,
+    store: Store,
+    body: NewApiKey
+) -> Result<impl warp::Reply, warp::Rejection> {
+    require_login(&session)?;
+    match store.create_api_key(&session.account_id, body).await {
+        Ok(api_key) => Ok(warp::reply::with_status(
+            warp::reply::json(&api_key),
+            StatusCode::CREATED
+        )),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
+
+/*
+@desc List the API keys of the logged in account
+@path GET /account/api-keys
+ */
+#[utoipa::path(get, path = "/account/api-keys", tag = "accounts", security(("bearer" = [])), responses((status = 200, body = Vec<ApiKey>)))]
+pub async fn get_api_keys(
+    session: Session,
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    require_login(&session)?;
+    match store.get_api_keys(&session.account_id).await {
+        Ok(api_keys) => Ok(warp::reply::json(&api_keys)),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
+
+/*
+@desc Revoke an API key of the logged in account
+@path DELETE /account/api-keys/{id}
+ */
+#[utoipa::path(delete, path = "/account/api-keys/{id}", tag = "accounts", security(("bearer" = [])), params(("id" = i32, Path, description = "Id of the API key")), responses((status = 200, body = ApiKey)))]
+pub async fn revoke_api_key(
+    id: i32,
+    session: Session,
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    require_login(&session)?;
+    match store.revoke_api_key(&session.account_id, id).await {
+        Ok(api_key) => Ok(warp::reply::json(&api_key)),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
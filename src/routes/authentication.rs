@@ -1,35 +1,42 @@
 use argon2::{self, Config};
 use chrono::prelude::*;
 use rand::Rng;
-use std::{env, future};
-use warp::{Filter};
+use std::env;
+use warp::{http::Method, Filter};
 
+use crate::mail::AccountMailer;
 use crate::store::Store;
-use crate::types::accounts::{Account, AccountId, Session};
+use crate::types::account_tokens::TokenPurpose;
+use crate::types::accounts::{AccessToken, Account, AccountId, Credentials, NewAccount, Session};
+use crate::types::api_keys::ApiScope;
+use crate::types::authorization::{parse_authorization, Authorization, BEARER_SCHEME};
+use crate::types::two_factor::TwoFactorChallenge;
 
 /*
-@desc Register a new user.
+@desc Register a new user and send the verification mail.
+A failed mail doesn't fail the registration, the mail can be sent again
 @path POST /registration
-@param account: Account struct with user information
+@param account: NewAccount struct with user information
 @return: JSON response with "Account added" on success, or error
 */
+#[utoipa::path(post, path = "/registration", tag = "accounts", request_body = NewAccount, responses((status = 200, description = "Account added, the verification mail is on its way", body = String)))]
 pub async fn register(
     store: Store,
-    account: Account
+    mailer: AccountMailer,
+    account: NewAccount
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let hashed_password = hash_password(account.password.as_bytes());
-    let account = Account {
-        id: account.id,
-        username: account.username,
-        password: hashed_password,
-        role: account.role
+    let password_hash = hash_password(account.password.as_bytes());
+    let (account, token) = match store.add_account(account, password_hash).await {
+        Ok(created) => created,
+        Err(e) => return Err(warp::reject::custom(e))
     };
-    match store.add_account(account).await {
-        Ok(_) => {
-            Ok(warp::reply::json(&"Account added".to_string()))
+
+    if let Some(email) = &account.email {
+        if let Err(e) = mailer.send_verification(email, &token).await {
+            tracing::event!(tracing::Level::ERROR, "{}", e);
         }
-        Err(e) => Err(warp::reject::custom(e))
     }
+    Ok(warp::reply::json(&"Account added".to_string()))
 }
 
 /*
@@ -37,7 +44,7 @@ pub async fn register(
 @param password: The password to hash
 @return String containing the hashed password
 */
-fn hash_password(password: &[u8]) -> String {
+pub(crate) fn hash_password(password: &[u8]) -> String {
     let salt = rand::thread_rng().gen::<[u8; 32]>();
     let config = Config::default();
     argon2::hash_encoded(password, &salt, &config).unwrap()
@@ -46,23 +53,34 @@ fn hash_password(password: &[u8]) -> String {
 
 /*
 @desc Login user with username and password.
+Accounts with two-factor authentication get a challenge token to complete at `POST /login/2fa`
 @path POST /login
-@param login: Account struct with username and password
-@return: JSON response with token on success, or error
+@param login: Credentials struct with username and password
+@return: JSON response with `access_token`, `token_type` and `expires_at` on success, or error
 */
+#[utoipa::path(post, path = "/login", tag = "accounts", request_body = Credentials, responses((status = 200, description = "Session token, or a challenge to complete at `POST /login/2fa` when two-factor authentication is on", body = AccessToken), (status = 401, description = "Wrong username or password", body = String, content_type = "text/plain")))]
 pub async fn login(
     store: Store,
-    login: Account
+    login: Credentials
 ) -> Result<impl warp::Reply, warp::Rejection> {
-     match store.get_account(login.username).await {
-         Ok(account) => match verify_password(
-             &account.password,
+     match store.clone().get_account_record(login.username).await {
+         Ok(record) => match verify_password(
+             &record.password_hash,
              login.password.as_bytes()
          ) {
              Ok(verified) => {
-                 if verified {
+                 if verified && record.totp_enabled_on.is_some() {
+                     let challenge_token = store
+                         .create_account_token(&record.id, TokenPurpose::TwoFactorChallenge)
+                         .await?;
+                     Ok(warp::reply::json(&TwoFactorChallenge {
+                         two_factor_required: true,
+                         challenge_token,
+                     }))
+                 } else if verified {
+                     let account = Account::from(record);
                      Ok(warp::reply::json(&issue_token(
-                         account.id.expect("id not found"),
+                         account.id,
                          // account.role
                      )))
                  } else {
@@ -85,7 +103,7 @@ pub async fn login(
 @param password: The password to verify
 @return true or argon2 error
 */
-fn verify_password(
+pub(crate) fn verify_password(
     hash: &str,
     password: &[u8],
 ) -> Result<bool, argon2::Error> {
@@ -95,38 +113,88 @@ fn verify_password(
 /*
 @desc Create a PASETO token with account ID and expiration date.
 @param account_id: The ID of the account
-@return String containing the generated token
+@return The generated token along its type and expiration date
 */
-fn issue_token(account_id: AccountId) -> String {
+pub(crate) fn issue_token(account_id: AccountId) -> AccessToken {
     let key = env::var("PASETO_KEY").unwrap();
 
     let current_date_time = Utc::now();
     let dt = current_date_time + chrono::Duration::days(1);
 
-    paseto::tokens::PasetoBuilder::new()
+    let access_token = paseto::tokens::PasetoBuilder::new()
         .set_encryption_key(&Vec::from(key.as_bytes()))
         .set_expiration(&dt)
         .set_not_before(&Utc::now())
         .set_claim("account_id", serde_json::json!(account_id))
         // .set_claim("role", serde_json::json!(role))
         .build()
-        .expect("Failed to construct paseto token w/ builder!")
+        .expect("Failed to construct paseto token w/ builder!");
+
+    AccessToken {
+        access_token,
+        token_type: BEARER_SCHEME.to_string(),
+        expires_at: dt,
+    }
 }
 
 /*
-@desc Authentication filter that verifies a PASETO token.
+@desc Authentication filter accepting `Bearer <token>`, a bare token for older clients, or `ApiKey <key>`.
+Requests made with an API key need the scope matching their method, 403 otherwise.
+A missing, malformed or invalid header is answered with 401
 @return: Filter
  */
 pub fn auth(
+    store: Store
 ) -> impl Filter<Extract = (Session,), Error = warp::Rejection> + Clone {
-    warp::header::<String>("Authorization").and_then(|token: String| {
-        let token = match verify_token(token) {
-            Ok(t) => t,
-            Err(_) => return future::ready(Err(warp::reject::reject())),
-        };
+    warp::header::optional::<String>("Authorization")
+        .and(warp::method())
+        .and_then(move |header: Option<String>, method: Method| {
+            let store = store.clone();
+            async move {
+                let header = header.ok_or_else(|| {
+                    handle_errors::Error::Unauthenticated("The Authorization header is missing".to_string())
+                })?;
+                match parse_authorization(&header)? {
+                    Authorization::Bearer(token) => verify_token(token).map_err(|_| {
+                        warp::reject::custom(handle_errors::Error::Unauthenticated(
+                            "The token is invalid or expired".to_string()
+                        ))
+                    }),
+                    Authorization::ApiKey(key) => verify_api_key(&store, &key, &method).await,
+                }
+            }
+        })
+}
 
-        future::ready(Ok(token))
-    })
+/*
+@desc Function to verify an API key and the scope needed by the request method
+@return A session if the key is valid, or error
+ */
+async fn verify_api_key(
+    store: &Store,
+    key: &str,
+    method: &Method
+) -> Result<Session, warp::Rejection> {
+    match store.authenticate_api_key(key).await? {
+        Some((session, scopes)) if scopes.contains(&ApiScope::for_method(method)) => Ok(session),
+        Some(_) => Err(warp::reject::custom(handle_errors::Error::Forbidden(format!(
+            "The API key lacks the {} scope", ApiScope::for_method(method).as_str()
+        )))),
+        None => Err(warp::reject::custom(handle_errors::Error::Unauthenticated(
+            "The API key is invalid, expired or revoked".to_string()
+        ))),
+    }
+}
+
+/*
+@desc Credentials and API keys can only be managed from a login session,
+so that a leaked key can't be used to mint more keys or turn protection off
+ */
+pub(crate) fn require_login(session: &Session) -> Result<(), handle_errors::Error> {
+    match session.api_key_id {
+        Some(_) => Err(handle_errors::Error::Unauthorized),
+        None => Ok(()),
+    }
 }
 
 /*
@@ -156,16 +224,30 @@ pub fn verify_token(
 
 #[cfg(test)]
 mod authentication_test {
-    use super::{auth, env, issue_token, AccountId};
+    use super::{auth, env, issue_token, AccountId, Store};
 
     #[tokio::test]
     async fn post_products_auth() {
         env::set_var("PASETO_KEY", "RANDOM WORDS WINTER MACINTOSH PC");
         let token = issue_token(AccountId(3));
-        let filter = auth();
+        // Session tokens are checked without a database round trip
+        let store = Store {
+            connection: sqlx::postgres::PgPoolOptions::new()
+                .connect_lazy("postgres://localhost/unused")
+                .unwrap(),
+            product_cache: crate::store::ProductCache::disabled(),
+            product_events: crate::store::ProductEvents::new(1),
+            changes: crate::store::ChangeFeed::new(),
+        };
+        let filter = auth(store);
         let res = warp::test::request()
-            .header("Authorization", token)
+            .header("Authorization", format!("Bearer {}", token.access_token))
             .filter(&filter);
         assert_eq!(res.await.unwrap().account_id, AccountId(3));
+
+        let res = warp::test::request()
+            .header("Authorization", "Bearer not-a-token")
+            .filter(&filter);
+        assert!(res.await.is_err());
     }
 }
\ No newline at end of file
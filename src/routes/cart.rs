@@ -0,0 +1,79 @@
+use warp::http::StatusCode;
+
+use crate::routes::idempotency::idempotent;
+use crate::store::Store;
+use crate::types::accounts::Session;
+use crate::types::cart::CartQuantity;
+use crate::types::idempotency::request_hash;
+
+/*
+@desc Get the cart of the logged in account, with a total per currency
+@path GET /cart
+ */
+#[utoipa::path(get, path = "/cart", tag = "cart", security(("bearer" = []), ("api_key" = [])), responses((status = 200, body = Cart)))]
+pub async fn get_cart(
+    session: Session,
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.get_cart(session.account_id).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
+
+/*
+@desc Put a product in the cart or set its quantity, sellers cannot add their own products
+@path PUT /cart/items/{product_id}
+ */
+#[utoipa::path(put, path = "/cart/items/{product_id}", tag = "cart", security(("bearer" = []), ("api_key" = [])), params(("product_id" = i32, Path, description = "Id of the product")), request_body = CartQuantity, responses((status = 200, body = Cart), (status = 403, description = "The product is one of your own", body = String, content_type = "text/plain")))]
+pub async fn set_cart_item(
+    product_id: i32,
+    session: Session,
+    store: Store,
+    cart_quantity: CartQuantity
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.set_cart_item(session.account_id, product_id, cart_quantity.quantity).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
+
+/*
+@desc Take a product out of the cart
+@path DELETE /cart/items/{product_id}
+ */
+#[utoipa::path(delete, path = "/cart/items/{product_id}", tag = "cart", security(("bearer" = []), ("api_key" = [])), params(("product_id" = i32, Path, description = "Id of the product")), responses((status = 200, description = "Product taken out of the cart", body = String, content_type = "text/plain")))]
+pub async fn remove_cart_item(
+    product_id: i32,
+    session: Session,
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.remove_cart_item(session.account_id, product_id).await {
+        Ok(_) => Ok(warp::reply::with_status(
+            format!("Product {} removed from cart", product_id),
+            StatusCode::OK
+        )),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
+
+/*
+@desc Check the cart against the current prices. Replies 409 when prices changed or products
+were deleted since they were added, the cart is then updated for the buyer to review it
+@path POST /cart/checkout
+ */
+#[utoipa::path(post, path = "/cart/checkout", tag = "cart", security(("bearer" = []), ("api_key" = [])), params(("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key replay the first response")), responses((status = 200, body = Cart), (status = 409, description = "The cart is empty, or prices changed or products disappeared and the cart was refreshed", body = String, content_type = "text/plain")))]
+pub async fn checkout(
+    session: Session,
+    idempotency_key: Option<String>,
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = session.account_id;
+    let hash = request_hash("POST /cart/checkout", &());
+    idempotent(&store.clone(), &account_id.clone(), idempotency_key, hash, || async move {
+        match store.checkout_cart(account_id).await {
+            Ok(res) => Ok(warp::reply::json(&res)),
+            Err(e) => Err(warp::reject::custom(e))
+        }
+    }).await
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use futures_util::{stream, StreamExt};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use warp::{http::StatusCode, hyper::body::Bytes, hyper::Body};
+
+use crate::openapi::{ExportQuery, ImportQuery};
+use crate::routes::idempotency::idempotent;
+use crate::store::Store;
+use crate::types::accounts::Session;
+use crate::types::events::{ProductEvent, ProductEventKind};
+use crate::types::catalog::{catalog_line, parse_catalog, CatalogFormat, ImportReport, CSV_HEADER};
+use crate::types::idempotency::raw_request_hash;
+
+/// Rows buffered between the database and a slow client during an export
+const EXPORT_BUFFER: usize = 64;
+
+/*
+@desc Import a catalog of products from CSV or NDJSON in a single transaction.
+Rows with the `id` of one of the seller's products replace it along its category and tags, so an
+export can be imported back. Nothing is imported when any row is invalid or refers to another
+seller's product or an unknown category, every such row is reported along its errors.
+With dry_run=true the catalog is checked and inserted, then rolled back.
+Only accounts with a verified email address can sell
+@path POST /products/import?format=csv|ndjson&dry_run=true
+@return: JSON import report, 422 when some rows are invalid
+ */
+#[utoipa::path(post, path = "/products/import", tag = "products", security(("bearer" = []), ("api_key" = [])), params(ImportQuery, ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key replay the first response")), request_body(content = String, description = "Catalog rows, CSV with a header line or one JSON object per line", content_type = "text/csv"), responses((status = 200, body = ImportReport), (status = 422, description = "Some rows are invalid, nothing was imported", body = ImportReport)))]
+pub async fn import_products(
+    session: Session,
+    params: HashMap<String, String>,
+    content_type: Option<String>,
+    idempotency_key: Option<String>,
+    store: Store,
+    body: Bytes
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = session.account_id;
+    if !store.is_email_verified(&account_id).await? {
+        return Err(warp::reject::custom(handle_errors::Error::EmailNotVerified));
+    }
+    let format = CatalogFormat::extract(&params, content_type.as_deref())?;
+    let dry_run = matches!(params.get("dry_run").map(String::as_str), Some("true") | Some("1"));
+    let hash = raw_request_hash(
+        &format!("POST /products/import?format={:?}&dry_run={}", format, dry_run),
+        &body,
+    );
+
+    idempotent(&store.clone(), &account_id.clone(), idempotency_key, hash, || async move {
+        let (products, errors) = parse_catalog(format, &body);
+        let total = products.len() + errors.len();
+        if !errors.is_empty() {
+            let report = ImportReport { dry_run, total, imported: 0, errors };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&report),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ));
+        }
+
+        match store.clone().import_products(products, account_id, dry_run).await {
+            Ok((_, errors)) if !errors.is_empty() => {
+                let report = ImportReport { dry_run, total, imported: 0, errors };
+                Ok(warp::reply::with_status(
+                    warp::reply::json(&report),
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                ))
+            }
+            Ok((imported, errors)) => {
+                if !dry_run {
+                    for (kind, product) in imported.iter() {
+                        store.product_events.publish(match kind {
+                            ProductEventKind::Created => ProductEvent::created(product),
+                            _ => ProductEvent::updated(product),
+                        });
+                    }
+                }
+                let report = ImportReport { dry_run, total, imported: imported.len(), errors };
+                Ok(warp::reply::with_status(warp::reply::json(&report), StatusCode::OK))
+            }
+            Err(e) => Err(warp::reject::custom(e))
+        }
+    }).await
+}
+
+/*
+@desc Stream the catalog of the logged in seller, rows are sent as they are read from database
+@path GET /products/export?format=csv|ndjson
+ */
+#[utoipa::path(get, path = "/products/export", tag = "products", security(("bearer" = []), ("api_key" = [])), params(ExportQuery), responses((status = 200, description = "The catalog of the seller, streamed", body = String, content_type = "text/csv")))]
+pub async fn export_products(
+    session: Session,
+    params: HashMap<String, String>,
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let format = CatalogFormat::extract(&params, None)?;
+    let (sender, receiver) = mpsc::channel(EXPORT_BUFFER);
+    tokio::spawn(store.export_products(session.account_id, sender));
+
+    let header = match format {
+        CatalogFormat::Csv => CSV_HEADER.as_bytes().to_vec(),
+        CatalogFormat::Ndjson => Vec::new(),
+    };
+    let lines = ReceiverStream::new(receiver).map(move |product| {
+        product
+            .and_then(|product| catalog_line(format, &product))
+            .map_err(|e| std::io::Error::other(e.to_string()))
+    });
+    let body = stream::once(async move { Ok(header) }).chain(lines);
+
+    let extension = match format {
+        CatalogFormat::Csv => "csv",
+        CatalogFormat::Ndjson => "ndjson",
+    };
+    warp::http::Response::builder()
+        .header("content-type", format.content_type())
+        .header(
+            "content-disposition",
+            format!("attachment; filename=\"catalog.{}\"", extension),
+        )
+        .body(Body::wrap_stream(body))
+        .map_err(|e| warp::reject::custom(handle_errors::Error::ExportError(e.to_string())))
+}
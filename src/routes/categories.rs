@@ -0,0 +1,92 @@
+use warp::http::StatusCode;
+
+use crate::routes::idempotency::idempotent;
+use crate::store::Store;
+use crate::types::accounts::Session;
+use crate::types::categories::NewCategory;
+use crate::types::idempotency::request_hash;
+
+/*
+@desc Get every category
+@path GET /categories
+ */
+#[utoipa::path(get, path = "/categories", tag = "categories", responses((status = 200, body = Vec<Category>)))]
+pub async fn get_categories(
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.get_categories().await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
+
+/*
+@desc Add a new category, admin only. Retries with the same Idempotency-Key are replayed
+@path POST /categories
+ */
+#[utoipa::path(post, path = "/categories", tag = "categories", security(("bearer" = []), ("api_key" = [])), params(("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key replay the first response")), request_body = NewCategory, responses((status = 200, body = Category), (status = 409, description = "A category with this name already exists under the same parent", body = String, content_type = "text/plain")))]
+pub async fn add_category(
+    session: Session,
+    idempotency_key: Option<String>,
+    store: Store,
+    new_category: NewCategory
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = session.account_id;
+    let hash = request_hash("POST /categories", &new_category);
+    idempotent(&store.clone(), &account_id.clone(), idempotency_key, hash, || async move {
+        if store.is_admin(&account_id).await? {
+            match store.add_category(new_category, account_id).await {
+                Ok(category) => Ok(warp::reply::json(&category)),
+                Err(e) => Err(warp::reject::custom(e))
+            }
+        } else {
+            Err(warp::reject::custom(handle_errors::Error::Unauthorized))
+        }
+    }).await
+}
+
+/*
+@desc Rename a category or move it below another parent, admin only
+@path PUT /categories/{id}
+ */
+#[utoipa::path(put, path = "/categories/{id}", tag = "categories", security(("bearer" = []), ("api_key" = [])), params(("id" = i32, Path, description = "Id of the category")), request_body = NewCategory, responses((status = 200, body = Category), (status = 409, description = "A category with this name already exists under the same parent, or the parent is the category or one of its subcategories", body = String, content_type = "text/plain")))]
+pub async fn update_category(
+    id: i32,
+    session: Session,
+    store: Store,
+    category: NewCategory
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = session.account_id;
+    if store.is_admin(&account_id).await? {
+        match store.update_category(id, category, account_id).await {
+            Ok(res) => Ok(warp::reply::json(&res)),
+            Err(e) => Err(warp::reject::custom(e))
+        }
+    } else {
+        Err(warp::reject::custom(handle_errors::Error::Unauthorized))
+    }
+}
+
+/*
+@desc Delete a category, admin only. Categories with subcategories are answered with 409
+@path DELETE /categories/{id}
+ */
+#[utoipa::path(delete, path = "/categories/{id}", tag = "categories", security(("bearer" = []), ("api_key" = [])), params(("id" = i32, Path, description = "Id of the category")), responses((status = 200, description = "Category deleted", body = String, content_type = "text/plain"), (status = 409, description = "The category has subcategories", body = String, content_type = "text/plain")))]
+pub async fn delete_category(
+    id: i32,
+    session: Session,
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = session.account_id;
+    if store.is_admin(&account_id).await? {
+        match store.delete_category(id, account_id).await {
+            Ok(_) => Ok(warp::reply::with_status(
+                format!("Category {} deleted", id),
+                StatusCode::OK
+            )),
+            Err(e) => Err(warp::reject::custom(e))
+        }
+    } else {
+        Err(warp::reject::custom(handle_errors::Error::Unauthorized))
+    }
+}
@@ -0,0 +1,44 @@
+use utoipa::OpenApi;
+
+use crate::openapi::ApiDoc;
+
+/// Swagger UI page loading `/v1/openapi.json`, the assets come from a CDN
+const DOCS_PAGE: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8" />
+  <title>Marketplace API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js" crossorigin></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({ url: "/v1/openapi.json", dom_id: "#swagger-ui" });
+    };
+  </script>
+</body>
+</html>
+"##;
+
+/*
+@desc OpenAPI 3 document of the API
+@path GET /openapi.json
+ */
+#[utoipa::path(get, path = "/openapi.json", tag = "docs", responses((status = 200, description = "OpenAPI 3 document", body = Object)))]
+pub async fn openapi_json() -> Result<impl warp::Reply, warp::Rejection> {
+    Ok(warp::reply::json(&ApiDoc::openapi()))
+}
+
+/*
+@desc Browsable documentation of the API, answers 404 when `api_docs` is turned off
+@path GET /docs
+ */
+#[utoipa::path(get, path = "/docs", tag = "docs", responses((status = 200, description = "Swagger UI page", body = String, content_type = "text/html")))]
+pub async fn docs_page(enabled: bool) -> Result<impl warp::Reply, warp::Rejection> {
+    if !enabled {
+        return Err(warp::reject::not_found());
+    }
+    Ok(warp::reply::html(DOCS_PAGE))
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use futures_util::{SinkExt, Stream, StreamExt};
+use warp::sse::Event;
+use warp::ws::{Message, WebSocket, Ws};
+
+use crate::openapi::EventQuery;
+use crate::store::{Delivery, Store};
+use crate::types::events::extract_event_filter;
+
+/// A WebSocket client that doesn't read for this long is disconnected, its events are dropped meanwhile
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn sse_event(delivery: Delivery) -> Result<Event, serde_json::Error> {
+    match delivery {
+        Delivery::Event(event) => Event::default().event(event.kind.as_str()).json_data(&event),
+        Delivery::Lagged(missed) => Ok(Event::default().event("lagged").data(missed.to_string())),
+        Delivery::Resync => Ok(Event::default().event("resync").data("")),
+    }
+}
+
+fn ws_message(delivery: &Delivery) -> Message {
+    let json = match delivery {
+        Delivery::Event(event) => serde_json::to_string(event),
+        Delivery::Lagged(missed) => serde_json::to_string(&serde_json::json!({ "kind": "lagged", "missed": missed })),
+        Delivery::Resync => serde_json::to_string(&serde_json::json!({ "kind": "resync" })),
+    };
+    Message::text(json.unwrap_or_default())
+}
+
+/*
+@desc Follow the product changes as Server-Sent Events, optionally those of a seller or of a product.
+Every event is named after its kind and carries the JSON of the change. A `lagged` event tells
+how many changes a slow client missed, it should reload the products it shows. So should it on a
+`resync` event, sent when the server may have missed the changes of another instance or when
+another instance imported products or changed the categories
+@path GET /products/stream?seller=&product=
+ */
+#[utoipa::path(get, path = "/products/stream", tag = "events", params(EventQuery), responses((status = 200, description = "`created`, `updated`, `deleted`, `lagged` and `resync` events", body = ProductEvent, content_type = "text/event-stream")))]
+pub async fn stream_products(
+    params: HashMap<String, String>,
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let filter = extract_event_filter(&params)?;
+    let events = store.product_events.subscribe(filter).map(sse_event);
+    Ok(warp::sse::reply(warp::sse::keep_alive().stream(events)))
+}
+
+/*
+@desc Follow the product changes over a WebSocket, one JSON text message per change.
+Lagged clients get `{"kind": "lagged", "missed": n}` and every client gets `{"kind": "resync"}` when the
+server may have missed changes or another instance changed products in bulk, messages sent by the client are ignored
+@path GET /products/stream/ws?seller=&product=
+ */
+#[utoipa::path(get, path = "/products/stream/ws", tag = "events", params(EventQuery), responses((status = 101, description = "Upgraded to a WebSocket sending `ProductEvent` messages")))]
+pub async fn product_socket(
+    ws: Ws,
+    params: HashMap<String, String>,
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let filter = extract_event_filter(&params)?;
+    let events = store.product_events.subscribe(filter);
+    Ok(ws.on_upgrade(move |socket| forward_events(socket, events)))
+}
+
+/// Send `events` until the client closes the socket, the connection drops or the client stops reading
+async fn forward_events(socket: WebSocket, events: impl Stream<Item = Delivery>) {
+    let (mut sender, mut receiver) = socket.split();
+    tokio::pin!(events);
+
+    loop {
+        tokio::select! {
+            delivery = events.next() => {
+                let Some(delivery) = delivery else { break };
+                match tokio::time::timeout(SEND_TIMEOUT, sender.send(ws_message(&delivery))).await {
+                    Ok(Ok(())) => (),
+                    _ => break,
+                }
+            }
+            incoming = receiver.next() => match incoming {
+                Some(Ok(message)) if message.is_close() => break,
+                Some(Ok(_)) => (),
+                _ => break,
+            }
+        }
+    }
+    let _ = sender.close().await;
+}
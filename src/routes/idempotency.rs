@@ -0,0 +1,125 @@
+use std::future::Future;
+
+use warp::{
+    http::{HeaderValue, StatusCode},
+    reply::Response,
+    Reply,
+};
+
+use handle_errors::{Error, FieldError};
+
+use crate::store::Store;
+use crate::types::accounts::AccountId;
+use crate::types::idempotency::{
+    IdempotencyState, StoredResponse, IDEMPOTENT_REPLAYED_HEADER, REPLAYED_HEADERS,
+};
+
+/*
+@desc Run a mutating handler at most once per `Idempotency-Key`. The first response is stored
+and replayed for retries of the same request, rejections release the key so that it can be retried.
+The response of a handler that went through is returned even when it cannot be stored.
+A key whose request never completed, say the instance crashed, can be claimed again once its lease ran out.
+@param key: Value of the Idempotency-Key header, without it the handler simply runs
+@param request_hash: Fingerprint of the request, see `types::idempotency::request_hash`
+@return The response of the handler, or the stored one
+ */
+pub async fn idempotent<F, Fut, R>(
+    store: &Store,
+    account_id: &AccountId,
+    key: Option<String>,
+    request_hash: String,
+    handler: F
+) -> Result<Response, warp::Rejection>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<R, warp::Rejection>>,
+    R: Reply,
+{
+    let key = match key {
+        Some(key) => key,
+        None => return handler().await.map(Reply::into_response),
+    };
+    if key.is_empty() || key.len() > 255 {
+        return Err(warp::reject::custom(Error::ValidationError(vec![FieldError {
+            field: "Idempotency-Key".to_string(),
+            rule: "length".to_string(),
+            message: "must be between 1 and 255 characters long".to_string(),
+        }])));
+    }
+
+    match store.begin_idempotent_request(account_id, &key, &request_hash).await? {
+        IdempotencyState::New => (),
+        IdempotencyState::Completed(stored) => return Ok(replay(stored)),
+        IdempotencyState::InProgress => {
+            return Err(warp::reject::custom(Error::IdempotencyKeyInProgress))
+        }
+        IdempotencyState::Mismatch => {
+            return Err(warp::reject::custom(Error::IdempotencyKeyReused))
+        }
+    }
+
+    let response = match handler().await {
+        Ok(reply) => reply.into_response(),
+        Err(rejection) => {
+            release(store, account_id, &key).await;
+            return Err(rejection);
+        }
+    };
+
+    let (parts, body) = response.into_parts();
+    let body = match warp::hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::event!(tracing::Level::ERROR, "Cannot buffer response: {:?}", e);
+            release(store, account_id, &key).await;
+            return Ok(Response::from_parts(parts, warp::hyper::Body::empty()));
+        }
+    };
+    let stored = StoredResponse {
+        status_code: parts.status.as_u16(),
+        headers: REPLAYED_HEADERS
+            .iter()
+            .filter_map(|name| {
+                parts
+                    .headers
+                    .get(*name)
+                    .and_then(|value| value.to_str().ok())
+                    .map(|value| (name.to_string(), value.to_string()))
+            })
+            .collect(),
+        body: body.to_vec(),
+    };
+    //The handler committed its changes, a failure to store its response mustn't turn them into an
+    //error the client would retry. The key then stays in progress until its lease runs out
+    if let Err(e) = store.complete_idempotent_request(account_id, &key, &request_hash, &stored).await {
+        tracing::event!(tracing::Level::ERROR, "Cannot store idempotent response: {}", e);
+    }
+
+    Ok(Response::from_parts(parts, body.into()))
+}
+
+/// Release the key of a failed request. Failing to release it is only logged, the error of the
+/// request matters more to the client and the key is freed anyway once the lease ran out
+async fn release(store: &Store, account_id: &AccountId, key: &str) {
+    if let Err(e) = store.release_idempotency_key(account_id, key).await {
+        tracing::event!(tracing::Level::ERROR, "Cannot release idempotency key: {}", e);
+    }
+}
+
+/// Rebuild a stored response, flagged as replayed
+fn replay(stored: StoredResponse) -> Response {
+    let mut response = Response::new(stored.body.into());
+    *response.status_mut() = StatusCode::from_u16(stored.status_code).unwrap_or(StatusCode::OK);
+    for (name, value) in stored.headers {
+        if let (Ok(name), Ok(value)) = (
+            warp::http::header::HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            response.headers_mut().insert(name, value);
+        }
+    }
+    response
+        .headers_mut()
+        .insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+    response
+}
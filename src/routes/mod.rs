@@ -1,2 +1,15 @@
+pub mod accounts;
+pub mod api_keys;
+pub mod admin;
 pub mod authentication;
-pub mod products;
\ No newline at end of file
+pub mod cart;
+pub mod catalog;
+pub mod categories;
+pub mod docs;
+pub mod events;
+pub mod idempotency;
+pub mod products;
+pub mod reviews;
+pub mod sellers;
+pub mod two_factor;
+pub mod validation;
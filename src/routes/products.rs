@@ -3,47 +3,135 @@ use std::collections::HashMap;
 use tracing::{event, instrument, Level};
 use warp::http::StatusCode;
 
+use crate::openapi::{ProductQuery, SearchQuery};
 use crate::store::Store;
-use crate::types::accounts::Session;
+use crate::types::accounts::{AccountId, Session};
+use crate::routes::idempotency::idempotent;
+use crate::types::events::ProductEvent;
+use crate::types::etag::{http_date, product_etag, IfMatch};
+use crate::types::idempotency::request_hash;
 use crate::types::pagination::{extract_pagination, Pagination};
-use crate::types::products::{NewProducts, Products};
+use crate::types::search::extract_search_query;
+use crate::types::products::{
+    extract_product_filter, normalize_tags, NewProducts, ProductCategory, ProductTags,
+    Products, UpdateProduct,
+};
+
+/// Reply with a product along its `ETag`, to be sent back in `If-Match` when updating it,
+/// and its `Last-Modified`. Both move with the reviews too, the rating is part of the product,
+/// but `If-Match` only checks the part of the `ETag` the seller changes
+fn product_reply(product: &Products) -> impl warp::Reply {
+    warp::reply::with_header(
+        warp::reply::with_header(
+            warp::reply::json(product),
+            "ETag",
+            product_etag(product.version, product.reviews_version),
+        ),
+        "Last-Modified",
+        http_date(&product.updated_on),
+    )
+}
+
+/// Whether the account owns the product. A product that doesn't exist is answered with 404
+/// rather than 401, soft deleted ones only count when `include_deleted`
+async fn owns_product(
+    store: &Store,
+    id: i32,
+    account_id: &AccountId,
+    include_deleted: bool
+) -> Result<bool, handle_errors::Error> {
+    if !store.product_exists(id, include_deleted).await? {
+        return Err(handle_errors::Error::NotFound);
+    }
+    store.is_product_owner(id, account_id).await
+}
 
 /*
-@desc get a limit number of products
-@path GET /products
+@desc get a limit number of products, optionally filtered by category (descendants included), tag and seller.
+Products come along their average rating and review count, sort=rating puts the best rated first
+@path GET /products?limit=&offset=&category=&tag=&seller=&sort=id|rating
  */
 #[instrument]
+#[utoipa::path(get, path = "/products", tag = "products", params(ProductQuery), responses((status = 200, body = Vec<Products>)))]
 pub async fn get_products(
     params: HashMap<String, String>,
     store: Store
 ) -> Result<impl warp::Reply, warp::Rejection> {
     event!(target: "restful-api", Level::INFO, "querying products");
+    let filter = extract_product_filter(&params)?;
     let mut pagination = Pagination::default();
-    if !params.is_empty() {
+    if params.contains_key("limit") || params.contains_key("offset") {
         event!(Level::INFO, pagination = true);
         pagination = extract_pagination(params)?;
     }
-    
-    match store.get_product(pagination.limit, pagination.offset).await {
+
+    match store.get_product(pagination.limit, pagination.offset, filter).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
+
+/*
+@desc full-text search over product names and descriptions, best matches first.
+Every result carries its rank and a snippet with the matching words highlighted
+@path GET /products/search?q=&limit=&offset=
+ */
+#[utoipa::path(get, path = "/products/search", tag = "products", params(SearchQuery), responses((status = 200, body = Vec<SearchResult>)))]
+pub async fn search_products(
+    params: HashMap<String, String>,
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let query = extract_search_query(&params)?;
+    let mut pagination = Pagination::default();
+    if params.contains_key("limit") || params.contains_key("offset") {
+        pagination = extract_pagination(params)?;
+    }
+
+    match store.search_products(query, pagination.limit, pagination.offset).await {
         Ok(res) => Ok(warp::reply::json(&res)),
         Err(e) => Err(warp::reject::custom(e))
     }
 }
 
 /*
-@desc update product information
-@path PUT /products
+@desc get a single product, along its ETag
+@path GET /products/{id}
  */
+#[utoipa::path(get, path = "/products/{id}", tag = "products", params(("id" = i32, Path, description = "Id of the product")), responses((status = 200, description = "The product, its `ETag` header is sent back in `If-Match` to update it", body = Products)))]
+pub async fn get_product(
+    id: i32,
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.get_product_by_id(id).await {
+        Ok(res) => Ok(product_reply(&res)),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
+
+/*
+@desc partially update product information, omitted fields are left untouched.
+The If-Match header must carry the ETag of the product, so that concurrent edits aren't overwritten
+@path PUT|PATCH /products/{id}
+ */
+#[utoipa::path(put, path = "/products/{id}", tag = "products", security(("bearer" = []), ("api_key" = [])), params(("id" = i32, Path, description = "Id of the product"), ("If-Match" = String, Header, description = "`ETag` of the product")), request_body = UpdateProduct, responses((status = 200, body = Products), (status = 412, description = "The product changed since the `ETag` was read", body = String, content_type = "text/plain"), (status = 428, description = "`If-Match` is missing", body = String, content_type = "text/plain")))]
 pub async fn update_product(
     id: i32,
     session: Session,
+    if_match: Option<String>,
     store: Store,
-    products: Products
+    products: UpdateProduct
 ) -> Result<impl warp::Reply, warp::Rejection> {
     let account_id = session.account_id;
-    if store.is_product_owner(id, &account_id).await? {
-        match store.update_product(products, id, account_id.0).await {
-            Ok(res) => Ok(warp::reply::json(&res)),
+    let if_match = match if_match {
+        Some(header) => IfMatch::parse(&header),
+        None => return Err(warp::reject::custom(handle_errors::Error::PreconditionRequired)),
+    };
+    if owns_product(&store, id, &account_id, false).await? {
+        match store.clone().update_product(products, id, account_id.0, if_match).await {
+            Ok(res) => {
+                store.product_events.publish(ProductEvent::updated(&res));
+                Ok(product_reply(&res))
+            }
             Err(e) => Err(warp::reject::custom(e))
         }
     } else {
@@ -52,21 +140,25 @@ pub async fn update_product(
 }
 
 /*
-@desc Delete a product
-@path DELETE /products
+@desc Soft delete a product, it can be brought back with the restore route
+@path DELETE /products/{id}
  */
+#[utoipa::path(delete, path = "/products/{id}", tag = "products", security(("bearer" = []), ("api_key" = [])), params(("id" = i32, Path, description = "Id of the product")), responses((status = 200, description = "Product deleted", body = String, content_type = "text/plain")))]
 pub async fn delete_product(
     id: i32,
     session: Session,
     store: Store
 ) -> Result<impl warp::Reply, warp::Rejection> {
     let account_id = session.account_id;
-    if store.is_product_owner(id, &account_id).await? {
-        match store.delete_product(id, account_id).await {
-            Ok(_) => Ok(warp::reply::with_status(
-                format!("Question {} deleted", id),
-                StatusCode::OK
-            )),
+    if owns_product(&store, id, &account_id, false).await? {
+        match store.clone().delete_product(id, account_id.clone()).await {
+            Ok(_) => {
+                store.product_events.publish(ProductEvent::deleted(id, Some(account_id)));
+                Ok(warp::reply::with_status(
+                    format!("Product {} deleted", id),
+                    StatusCode::OK
+                ))
+            }
             Err(e) => Err(warp::reject::custom(e))
         }
     } else {
@@ -75,21 +167,117 @@ pub async fn delete_product(
 }
 
 /*
-@desc Add a new product
+@desc Restore a soft deleted product, retries with the same Idempotency-Key are replayed
+@path POST /products/{id}/restore
+ */
+#[utoipa::path(post, path = "/products/{id}/restore", tag = "products", security(("bearer" = []), ("api_key" = [])), params(("id" = i32, Path, description = "Id of the product"), ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key replay the first response")), responses((status = 200, body = Products)))]
+pub async fn restore_product(
+    id: i32,
+    session: Session,
+    idempotency_key: Option<String>,
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = session.account_id;
+    if !store.is_email_verified(&account_id).await? {
+        return Err(warp::reject::custom(handle_errors::Error::EmailNotVerified));
+    }
+    let hash = request_hash(&format!("POST /products/{}/restore", id), &());
+    idempotent(&store.clone(), &account_id.clone(), idempotency_key, hash, || async move {
+        if owns_product(&store, id, &account_id, true).await? {
+            match store.clone().restore_product(id, account_id).await {
+                Ok(res) => {
+                    store.product_events.publish(ProductEvent::created(&res));
+                    Ok(product_reply(&res))
+                }
+                Err(e) => Err(warp::reject::custom(e))
+            }
+        } else {
+            Err(warp::reject::custom(handle_errors::Error::Unauthorized))
+        }
+    }).await
+}
+
+/*
+@desc Add a new product, retries with the same Idempotency-Key are replayed instead of creating duplicates.
+Only accounts with a verified email address can sell
 @path POST /products
  */
+#[utoipa::path(post, path = "/products", tag = "products", security(("bearer" = []), ("api_key" = [])), params(("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key replay the first response")), request_body = NewProducts, responses((status = 200, body = Products), (status = 403, description = "The email address of the account isn't verified", body = String, content_type = "text/plain")))]
 pub async fn add_product(
     session: Session,
+    idempotency_key: Option<String>,
     store: Store,
     new_products: NewProducts
 ) -> Result<impl warp::Reply, warp::Rejection> {
     let account_id = session.account_id;
-    let product = NewProducts {
-        name: new_products.name,
-        price: new_products.price,
-    };
-    match store.add_product(product, account_id).await {
-        Ok(product) => Ok(warp::reply::json(&product)),
-        Err(e) => Err(warp::reject::custom(e))
+    if !store.is_email_verified(&account_id).await? {
+        return Err(warp::reject::custom(handle_errors::Error::EmailNotVerified));
+    }
+    let hash = request_hash("POST /products", &new_products);
+    idempotent(&store.clone(), &account_id.clone(), idempotency_key, hash, || async move {
+        let product = NewProducts {
+            name: new_products.name,
+            description: new_products.description,
+            price: new_products.price,
+        };
+        match store.clone().add_product(product, account_id).await {
+            Ok(product) => {
+                store.product_events.publish(ProductEvent::created(&product));
+                Ok(product_reply(&product))
+            }
+            Err(e) => Err(warp::reject::custom(e))
+        }
+    }).await
+}
+
+/*
+@desc Assign one of the seller's products to a category
+@path PUT /products/{id}/category
+ */
+#[utoipa::path(put, path = "/products/{id}/category", tag = "products", security(("bearer" = []), ("api_key" = [])), params(("id" = i32, Path, description = "Id of the product")), request_body = ProductCategory, responses((status = 200, body = Products)))]
+pub async fn set_product_category(
+    id: i32,
+    session: Session,
+    store: Store,
+    category: ProductCategory
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = session.account_id;
+    if owns_product(&store, id, &account_id, false).await? {
+        match store.clone().set_product_category(id, category.category_id, account_id).await {
+            Ok(res) => {
+                store.product_events.publish(ProductEvent::updated(&res));
+                Ok(product_reply(&res))
+            }
+            Err(e) => Err(warp::reject::custom(e))
+        }
+    } else {
+        Err(warp::reject::custom(handle_errors::Error::Unauthorized))
     }
-}
\ No newline at end of file
+}
+
+/*
+@desc Replace the tags of one of the seller's products
+@path PUT /products/{id}/tags
+ */
+#[utoipa::path(put, path = "/products/{id}/tags", tag = "products", security(("bearer" = []), ("api_key" = [])), params(("id" = i32, Path, description = "Id of the product")), request_body = ProductTags, responses((status = 200, body = Products)))]
+pub async fn set_product_tags(
+    id: i32,
+    session: Session,
+    store: Store,
+    tags: ProductTags
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = session.account_id;
+    let tags = normalize_tags(&tags.0);
+
+    if owns_product(&store, id, &account_id, false).await? {
+        match store.clone().set_product_tags(id, tags, account_id).await {
+            Ok(res) => {
+                store.product_events.publish(ProductEvent::updated(&res));
+                Ok(product_reply(&res))
+            }
+            Err(e) => Err(warp::reject::custom(e))
+        }
+    } else {
+        Err(warp::reject::custom(handle_errors::Error::Unauthorized))
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use warp::http::StatusCode;
+
+use crate::openapi::PaginationQuery;
+use crate::routes::idempotency::idempotent;
+use crate::store::Store;
+use crate::types::accounts::Session;
+use crate::types::idempotency::request_hash;
+use crate::types::pagination::{extract_pagination, Pagination};
+use crate::types::reviews::NewReview;
+
+/*
+@desc Get the reviews of a product, newest first
+@path GET /products/{id}/reviews?limit=&offset=
+ */
+#[utoipa::path(get, path = "/products/{id}/reviews", tag = "reviews", params(("id" = i32, Path, description = "Id of the product"), PaginationQuery), responses((status = 200, body = Vec<Review>)))]
+pub async fn get_reviews(
+    product_id: i32,
+    params: HashMap<String, String>,
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut pagination = Pagination::default();
+    if params.contains_key("limit") || params.contains_key("offset") {
+        pagination = extract_pagination(params)?;
+    }
+
+    store.clone().get_product_by_id(product_id).await?;
+    match store.get_reviews(product_id, pagination.limit, pagination.offset).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
+
+/*
+@desc Review a product, once per account and never one of your own products.
+Retries with the same Idempotency-Key are replayed
+@path POST /products/{id}/reviews
+ */
+#[utoipa::path(post, path = "/products/{id}/reviews", tag = "reviews", security(("bearer" = []), ("api_key" = [])), params(("id" = i32, Path, description = "Id of the product"), ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key replay the first response")), request_body = NewReview, responses((status = 200, body = Review), (status = 403, description = "The product is one of your own", body = String, content_type = "text/plain"), (status = 409, description = "The product was already reviewed by this account", body = String, content_type = "text/plain")))]
+pub async fn add_review(
+    product_id: i32,
+    session: Session,
+    idempotency_key: Option<String>,
+    store: Store,
+    review: NewReview
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = session.account_id;
+    let hash = request_hash(&format!("POST /products/{}/reviews", product_id), &review);
+    idempotent(&store.clone(), &account_id.clone(), idempotency_key, hash, || async move {
+        match store.add_review(product_id, review, account_id).await {
+            Ok(res) => Ok(warp::reply::json(&res)),
+            Err(e) => Err(warp::reject::custom(e))
+        }
+    }).await
+}
+
+/*
+@desc Edit a review, author only
+@path PUT /reviews/{id}
+ */
+#[utoipa::path(put, path = "/reviews/{id}", tag = "reviews", security(("bearer" = []), ("api_key" = [])), params(("id" = i32, Path, description = "Id of the review")), request_body = NewReview, responses((status = 200, body = Review)))]
+pub async fn update_review(
+    id: i32,
+    session: Session,
+    store: Store,
+    review: NewReview
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.update_review(id, review, session.account_id).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
+
+/*
+@desc Delete a review, author or admin only
+@path DELETE /reviews/{id}
+ */
+#[utoipa::path(delete, path = "/reviews/{id}", tag = "reviews", security(("bearer" = []), ("api_key" = [])), params(("id" = i32, Path, description = "Id of the review")), responses((status = 200, description = "Review deleted", body = String, content_type = "text/plain")))]
+pub async fn delete_review(
+    id: i32,
+    session: Session,
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.delete_review(id, session.account_id).await {
+        Ok(_) => Ok(warp::reply::with_status(
+            format!("Review {} deleted", id),
+            StatusCode::OK
+        )),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
+
+#[cfg(test)]
+mod reviews_test {
+    use handle_errors::{return_error, Error};
+    use warp::http::StatusCode;
+
+    async fn status(error: Error) -> StatusCode {
+        return_error(warp::reject::custom(error)).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn refused_reviews() {
+        assert_eq!(status(Error::AlreadyReviewed).await, StatusCode::CONFLICT);
+        assert_eq!(status(Error::OwnProduct).await, StatusCode::FORBIDDEN);
+    }
+}
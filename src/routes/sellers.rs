@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+
+use crate::openapi::SellerProductQuery;
+use crate::store::Store;
+use crate::types::accounts::Session;
+use crate::types::pagination::{extract_pagination, Pagination};
+use crate::types::products::extract_product_filter;
+use crate::types::sellers::UpdateSellerProfile;
+
+/*
+@desc Get the public profile of a seller, accounts which neither have the seller role nor listed a product are answered with 404
+@path GET /sellers/{id}
+ */
+#[utoipa::path(get, path = "/sellers/{id}", tag = "sellers", params(("id" = i32, Path, description = "Id of the seller account")), responses((status = 200, body = SellerProfile)))]
+pub async fn get_seller(
+    id: i32,
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.get_seller_profile(id).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
+
+/*
+@desc Get the products of a seller, with the same filters and pagination as GET /products
+@path GET /sellers/{id}/products?limit=&offset=&category=&tag=&sort=
+ */
+#[utoipa::path(get, path = "/sellers/{id}/products", tag = "sellers", params(("id" = i32, Path, description = "Id of the seller account"), SellerProductQuery), responses((status = 200, body = Vec<Products>)))]
+pub async fn get_seller_products(
+    id: i32,
+    params: HashMap<String, String>,
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut filter = extract_product_filter(&params)?;
+    filter.seller_id = Some(id);
+    let mut pagination = Pagination::default();
+    if params.contains_key("limit") || params.contains_key("offset") {
+        pagination = extract_pagination(params)?;
+    }
+
+    store.clone().get_seller_profile(id).await?;
+    match store.get_product(pagination.limit, pagination.offset, filter).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
+
+/*
+@desc Edit the public profile of the logged in account
+@path PATCH /sellers/me
+ */
+#[utoipa::path(patch, path = "/sellers/me", tag = "sellers", security(("bearer" = []), ("api_key" = [])), request_body = UpdateSellerProfile, responses((status = 200, body = SellerProfile)))]
+pub async fn update_seller_profile(
+    session: Session,
+    store: Store,
+    profile: UpdateSellerProfile
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.update_seller_profile(session.account_id, profile).await {
+        Ok(res) => Ok(warp::reply::json(&res)),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
@@ -0,0 +1,87 @@
+use warp::http::StatusCode;
+
+use crate::routes::authentication::{issue_token, require_login, verify_password};
+use crate::store::Store;
+use crate::types::accounts::Session;
+use crate::types::two_factor::{DisableTwoFactor, TotpCode, TwoFactorLogin};
+
+/*
+@desc Start enrolling an authenticator app. The reply holds the secret and the otpauth URI,
+two-factor authentication is only enabled once a first code is confirmed
+@path POST /account/2fa/enroll
+ */
+#[utoipa::path(post, path = "/account/2fa/enroll", tag = "accounts", security(("bearer" = [])), responses((status = 200, body = TotpEnrollment), (status = 409, description = "Two-factor authentication is already enabled", body = String, content_type = "text/plain")))]
+pub async fn enroll_two_factor(
+    session: Session,
+    store: Store
+) -> Result<impl warp::Reply, warp::Rejection> {
+    require_login(&session)?;
+    match store.enroll_two_factor(&session.account_id).await {
+        Ok(enrollment) => Ok(warp::reply::json(&enrollment)),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
+
+/*
+@desc Enable two-factor authentication with a first code of the enrolled app,
+the reply holds the backup codes which are never shown again
+@path POST /account/2fa/confirm
+ */
+#[utoipa::path(post, path = "/account/2fa/confirm", tag = "accounts", security(("bearer" = [])), request_body = TotpCode, responses((status = 201, description = "Two-factor authentication enabled", body = BackupCodes), (status = 409, description = "Two-factor authentication is already enabled", body = String, content_type = "text/plain")))]
+pub async fn confirm_two_factor(
+    session: Session,
+    store: Store,
+    body: TotpCode
+) -> Result<impl warp::Reply, warp::Rejection> {
+    require_login(&session)?;
+    match store.confirm_two_factor(&session.account_id, body.code).await {
+        Ok(backup_codes) => Ok(warp::reply::with_status(
+            warp::reply::json(&backup_codes),
+            StatusCode::CREATED
+        )),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
+
+/*
+@desc Turn two-factor authentication off, which takes the password along a code
+@path POST /account/2fa/disable
+ */
+#[utoipa::path(post, path = "/account/2fa/disable", tag = "accounts", security(("bearer" = [])), request_body = DisableTwoFactor, responses((status = 200, description = "Two-factor authentication disabled", body = String, content_type = "text/plain"), (status = 400, description = "Two-factor authentication is not enabled", body = String, content_type = "text/plain")))]
+pub async fn disable_two_factor(
+    session: Session,
+    store: Store,
+    body: DisableTwoFactor
+) -> Result<impl warp::Reply, warp::Rejection> {
+    require_login(&session)?;
+    let record = store.get_account_record_by_id(&session.account_id).await?;
+    match verify_password(&record.password_hash, body.password.as_bytes()) {
+        Ok(true) => (),
+        Ok(false) => return Err(warp::reject::custom(handle_errors::Error::WrongPassword)),
+        Err(e) => return Err(warp::reject::custom(handle_errors::Error::ArgonLibraryError(e))),
+    }
+
+    match store.disable_two_factor(&session.account_id, body.code).await {
+        Ok(_) => Ok(warp::reply::with_status(
+            "Two-factor authentication disabled".to_string(),
+            StatusCode::OK
+        )),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
+
+/*
+@desc Second step of the login of accounts with two-factor authentication:
+trade the challenge token of `POST /login` and a code for a session token
+@path POST /login/2fa
+ */
+#[utoipa::path(post, path = "/login/2fa", tag = "accounts", request_body = TwoFactorLogin, responses((status = 200, body = AccessToken)))]
+pub async fn login_two_factor(
+    store: Store,
+    body: TwoFactorLogin
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.complete_two_factor_login(body.challenge_token, body.code).await {
+        Ok(account_id) => Ok(warp::reply::json(&issue_token(account_id))),
+        Err(e) => Err(warp::reject::custom(e))
+    }
+}
@@ -0,0 +1,63 @@
+use serde::de::DeserializeOwned;
+use warp::Filter;
+
+use crate::types::validation::Validate;
+
+/// Largest JSON body accepted by the API, in bytes
+pub const MAX_BODY_SIZE: u64 = 64 * 1024;
+
+/*
+@desc JSON body filter which bounds the body size, then deserializes and validates it.
+@return: Filter extracting the validated body, or rejecting with the broken rules
+ */
+pub fn json_body<T>() -> impl Filter<Extract = (T,), Error = warp::Rejection> + Clone
+where
+    T: DeserializeOwned + Validate + Send,
+{
+    warp::body::content_length_limit(MAX_BODY_SIZE)
+        .and(warp::body::json())
+        .and_then(|body: T| async move {
+            match body.validate() {
+                Ok(_) => Ok(body),
+                Err(e) => Err(warp::reject::custom(e)),
+            }
+        })
+}
+
+#[cfg(test)]
+mod validation_test {
+    use super::{json_body, MAX_BODY_SIZE};
+    use crate::types::products::NewProducts;
+
+    #[tokio::test]
+    async fn valid_body() {
+        let res = warp::test::request()
+            .body(r#"{"name": "sample", "price": {"amount": 100}}"#)
+            .filter(&json_body::<NewProducts>())
+            .await;
+        assert_eq!(res.unwrap().name, "sample");
+    }
+
+    #[tokio::test]
+    async fn invalid_body() {
+        let res = warp::test::request()
+            .body(r#"{"name": "", "price": {"amount": -100}}"#)
+            .filter(&json_body::<NewProducts>())
+            .await;
+        let rejection = res.unwrap_err();
+        match rejection.find::<handle_errors::Error>() {
+            Some(handle_errors::Error::ValidationError(errors)) => assert_eq!(errors.len(), 2),
+            e => panic!("unexpected rejection {:?}", e),
+        }
+    }
+
+    #[tokio::test]
+    async fn body_too_large() {
+        let name = "a".repeat(MAX_BODY_SIZE as usize);
+        let res = warp::test::request()
+            .body(format!(r#"{{"name": "{}", "price": {{"amount": 100}}}}"#, name))
+            .filter(&json_body::<NewProducts>())
+            .await;
+        assert!(res.unwrap_err().find::<warp::reject::PayloadTooLarge>().is_some());
+    }
+}
@@ -1,18 +1,165 @@
 use sqlx::{
     postgres::{PgPool, PgPoolOptions, PgRow},
-    Row,
+    PgConnection, Postgres, QueryBuilder, Row, Transaction,
 };
 
 use handle_errors::Error;
 
 use crate::types::{
-    accounts::{Account, AccountId},
+    account_tokens::TokenPurpose,
+    accounts::{normalize_email, Account, AccountId, AccountRecord, NewAccount, ADMIN_ROLE},
+    audit::{AuditAction, NewAuditEvent},
+    categories::{Category, CategoryId, NewCategory},
+    events::{ProductEventKind, StoreChange},
 };
-use crate::types::products::{NewProducts, ProductId, Products};
+use crate::types::products::{
+    NewProducts, ProductFilter, ProductId, ProductRecord, ProductSort, Products, UpdateProduct,
+};
+use crate::types::etag::IfMatch;
+
+mod account_tokens;
+mod api_keys;
+mod audit;
+mod cache;
+mod cart;
+mod catalog;
+mod events;
+mod idempotency;
+mod notify;
+mod reviews;
+mod search;
+mod sellers;
+mod two_factor;
+
+pub(crate) use account_tokens::issue_token;
+pub(crate) use audit::record_event;
+pub(crate) use notify::notify;
+pub use cache::ProductCache;
+pub use events::{Delivery, ProductEvents, DEFAULT_EVENT_BUFFER};
+pub use notify::ChangeFeed;
+
+/// Columns selected for every product query, `p` being the alias of the `products` table
+pub(crate) const PRODUCT_COLUMNS: &str = "p.id, p.name, p.description, p.price_amount, p.currency, p.seller_id, \
+    p.category_id, p.created_on, p.updated_on, p.deleted_at, p.version, p.reviews_version, \
+    ARRAY(SELECT t.name::text FROM product_tags pt JOIN tags t ON t.id = pt.tag_id \
+    WHERE pt.product_id = p.id ORDER BY t.name) AS tags, \
+    (SELECT AVG(r.rating)::FLOAT8 FROM reviews r WHERE r.product_id = p.id) AS average_rating, \
+    (SELECT COUNT(*) FROM reviews r WHERE r.product_id = p.id) AS review_count";
+
+fn product_record_from_row(row: PgRow) -> ProductRecord {
+    ProductRecord {
+        id: ProductId(row.get("id")),
+        name: row.get("name"),
+        description: row.get("description"),
+        price_amount: row.get("price_amount"),
+        currency: row.get("currency"),
+        seller_id: row.get::<Option<i32>, _>("seller_id").map(AccountId),
+        category_id: row.get::<Option<i32>, _>("category_id").map(CategoryId),
+        tags: row.get("tags"),
+        average_rating: row.get("average_rating"),
+        review_count: row.get("review_count"),
+        created_on: row.get("created_on"),
+        updated_on: row.get("updated_on"),
+        deleted_at: row.get("deleted_at"),
+        version: row.get("version"),
+        reviews_version: row.get("reviews_version"),
+    }
+}
+
+/// Map a row selected with `PRODUCT_COLUMNS` to the product handed out by the store
+pub(crate) fn product_from_row(row: PgRow) -> Products {
+    product_record_from_row(row).into()
+}
+
+pub(crate) fn account_record_from_row(row: PgRow) -> AccountRecord {
+    AccountRecord {
+        id: AccountId(row.get("id")),
+        username: row.get("username"),
+        password_hash: row.get("password"),
+        role: row.get("role"),
+        email: row.get("email"),
+        email_verified_on: row.get("email_verified_on"),
+        totp_enabled_on: row.get("totp_enabled_on"),
+        created_on: row.get("created_on"),
+    }
+}
+
+fn category_from_row(row: PgRow) -> Category {
+    Category {
+        id: CategoryId(row.get("id")),
+        name: row.get("name"),
+        parent_id: row.get::<Option<i32>, _>("parent_id").map(CategoryId),
+    }
+}
+
+/// Lock a product row for the rest of the transaction and return its current state
+pub(crate) async fn lock_product(
+    connection: &mut PgConnection,
+    id: i32
+) -> Result<Option<Products>, Error> {
+    sqlx::query(&format!(
+        "SELECT {} FROM products p WHERE p.id = $1 FOR UPDATE OF p",
+        PRODUCT_COLUMNS
+    ))
+        .bind(id)
+        .map(product_from_row)
+        .fetch_optional(connection)
+        .await
+        .map_err(Error::DatabaseQueryError)
+}
+
+/// Replace the tags of a product, creating the tags which don't exist yet
+pub(crate) async fn replace_product_tags(
+    connection: &mut PgConnection,
+    id: i32,
+    tags: &[String]
+) -> Result<(), Error> {
+    sqlx::query("INSERT INTO tags (name) SELECT unnest($1::text[]) ON CONFLICT (name) DO NOTHING")
+        .bind(tags)
+        .execute(&mut *connection)
+        .await
+        .map_err(Error::DatabaseQueryError)?;
+    sqlx::query("DELETE FROM product_tags WHERE product_id = $1")
+        .bind(id)
+        .execute(&mut *connection)
+        .await
+        .map_err(Error::DatabaseQueryError)?;
+    sqlx::query("INSERT INTO product_tags (product_id, tag_id) \
+        SELECT $1, id FROM tags WHERE name = ANY($2)")
+        .bind(id)
+        .bind(tags)
+        .execute(connection)
+        .await
+        .map_err(Error::DatabaseQueryError)?;
+    Ok(())
+}
+
+/// Errors of the statements writing a category, a name taken under the same parent is a conflict
+fn category_error(error: sqlx::Error) -> Error {
+    tracing::event!(tracing::Level::ERROR, "{:?}", error);
+    match error.as_database_error() {
+        Some(database_error) if database_error.is_unique_violation() => Error::CategoryExists,
+        _ => Error::DatabaseQueryError(error),
+    }
+}
+
+/// Commit a transaction, logging the failure if any
+pub(crate) async fn commit(tx: Transaction<'_, Postgres>) -> Result<(), Error> {
+    match tx.commit().await {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            tracing::event!(tracing::Level::ERROR, "{:?}", error);
+            Err(Error::DatabaseQueryError(error))
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Store {
     pub(crate) connection: PgPool,
+    pub(crate) product_cache: ProductCache,
+    pub(crate) product_events: ProductEvents,
+    pub(crate) changes: ChangeFeed,
 }
 
 impl Store {
@@ -25,24 +172,49 @@ impl Store {
             .connect(db_url)
             .await?;
         Ok(Store{
-            connection: db_pool
+            connection: db_pool,
+            product_cache: ProductCache::disabled(),
+            product_events: ProductEvents::new(DEFAULT_EVENT_BUFFER),
+            changes: ChangeFeed::new()
         })
     }
 
-    ///Insert to database new account
+    ///Serve product reads from `cache`, the mutations of the store keep it current
+    pub fn with_product_cache(self, cache: ProductCache) -> Self {
+        Store {
+            product_cache: cache,
+            ..self
+        }
+    }
+
+    ///Broadcast the product changes to the streams subscribed to `events`
+    pub fn with_product_events(self, events: ProductEvents) -> Self {
+        Store {
+            product_events: events,
+            ..self
+        }
+    }
+
+    ///Insert to database new account along its email verification token, the account is the actor
+    ///of its own registration event
     pub async fn add_account(
         self,
-        account: Account
-    ) -> Result<bool, Error> {
-        match sqlx::query(
-            "INSERT INTO accounts (username, password, role) VALUES ($1, $2, $3)"
+        account: NewAccount,
+        password_hash: String
+    ) -> Result<(Account, String), Error> {
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+
+        let account: Account = match sqlx::query(
+            "INSERT INTO accounts (username, password, role, email) VALUES ($1, $2, $3, $4) RETURNING *"
         )
             .bind(account.username)
-            .bind(account.password)
+            .bind(password_hash)
             .bind(account.role)
-            .execute(&self.connection)
+            .bind(normalize_email(&account.email))
+            .map(account_record_from_row)
+            .fetch_one(&mut *tx)
             .await {
-            Ok(_) => Ok(true),
+            Ok(record) => record.into(),
             Err(error) => {
                 tracing::event!(
                     tracing::Level::ERROR,
@@ -61,24 +233,29 @@ impl Store {
                         .constraint()
                         .unwrap()
                 );
-                Err(Error::DatabaseQueryError(error))
+                return Err(Error::DatabaseQueryError(error));
             }
-        }
+        };
+
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(account.id.clone(), AuditAction::AccountRegister, account.id.0)
+                .after(Some(&account)),
+        ).await?;
+        let token = issue_token(&mut tx, &account.id, TokenPurpose::VerifyEmail).await?;
+        notify(&mut tx, &self.changes, StoreChange::account(&account.id, AuditAction::AccountRegister)).await?;
+        commit(tx).await?;
+        Ok((account, token))
     }
 
-    ///Get accounts from database
-    pub async fn get_account(
+    ///Get an account along its password hash, only meant to check credentials
+    pub async fn get_account_record(
         self,
         username: String
-    ) -> Result<Account, Error> {
+    ) -> Result<AccountRecord, Error> {
         match sqlx::query("SELECT * FROM accounts WHERE username = $1")
             .bind(username)
-            .map(|row: PgRow| Account {
-                id: Some(AccountId(row.get("id"))),
-                username: row.get("username"),
-                password: row.get("password"),
-                role: row.get("role")
-            })
+            .map(account_record_from_row)
             .fetch_one(&self.connection)
             .await {
             Ok(account) => Ok(account),
@@ -89,23 +266,89 @@ impl Store {
         }
     }
 
-    ///Get a limit number of products from database
+    ///Get a limit number of products from database, narrowed down by the given filter
     pub async fn get_product(
         self,
         limit: Option<i32>,
-        offset: i32
+        offset: i32,
+        filter: ProductFilter
     ) -> Result<Vec<Products>, Error> {
-        match sqlx::query("SELECT * FROM products LIMIT $1 OFFSET $2")
-            .bind(limit)
-            .bind(offset)
-            .map(|row: PgRow| Products {
-                id: ProductId(row.get("id")),
-                name: row.get("name"),
-                price: row.get("price"),
-            })
+        let key = (limit, offset, filter.clone());
+        let generation = match self.product_cache.get_list(&key) {
+            Ok(products) => return Ok(products),
+            Err(generation) => generation,
+        };
+
+        let mut query = QueryBuilder::<Postgres>::new(format!(
+            "SELECT {} FROM products p WHERE p.deleted_at IS NULL", PRODUCT_COLUMNS
+        ));
+        if let Some(category_id) = filter.category_id {
+            query
+                .push(" AND p.category_id IN (WITH RECURSIVE subtree AS (\
+                SELECT id FROM categories WHERE id = ")
+                .push_bind(category_id)
+                .push(" UNION ALL SELECT c.id FROM categories c JOIN subtree s ON c.parent_id = s.id) \
+                SELECT id FROM subtree)");
+        }
+        if let Some(tag) = filter.tag {
+            query
+                .push(" AND EXISTS (SELECT 1 FROM product_tags pt JOIN tags t ON t.id = pt.tag_id \
+                WHERE pt.product_id = p.id AND t.name = ")
+                .push_bind(tag)
+                .push(")");
+        }
+        if let Some(seller_id) = filter.seller_id {
+            query.push(" AND p.seller_id = ").push_bind(seller_id);
+        }
+        query.push(match filter.sort {
+            ProductSort::Id => " ORDER BY p.id",
+            ProductSort::Rating => " ORDER BY average_rating DESC NULLS LAST, review_count DESC, p.id",
+        });
+        query
+            .push(" LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+
+        match query
+            .build()
+            .map(product_from_row)
             .fetch_all(&self.connection)
             .await {
-            Ok(productions) => Ok(productions),
+            Ok(productions) => {
+                self.product_cache.put_list(generation, key, productions.clone());
+                Ok(productions)
+            }
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    ///Get a single product by its id, soft deleted products are not found
+    pub async fn get_product_by_id(
+        self,
+        id: i32
+    ) -> Result<Products, Error> {
+        let generation = match self.product_cache.get_product(id) {
+            Ok(product) => return Ok(product),
+            Err(generation) => generation,
+        };
+
+        match sqlx::query(&format!(
+            "SELECT {} FROM products p WHERE p.id = $1 AND p.deleted_at IS NULL",
+            PRODUCT_COLUMNS
+        ))
+            .bind(id)
+            .map(product_from_row)
+            .fetch_optional(&self.connection)
+            .await {
+            Ok(Some(product)) => {
+                self.product_cache.put_product(generation, product.clone());
+                Ok(product)
+            }
+            Ok(None) => Err(Error::NotFound),
             Err(e) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", e);
                 Err(Error::DatabaseQueryError(e))
@@ -119,75 +362,198 @@ impl Store {
         new_productions: NewProducts,
         account_id: AccountId
     ) -> Result<Products, Error> {
-        match sqlx::query("INSERT INTO products (name, price, seller_id) VALUES ($1, $2, $3) RETURNING id, name, price, seller_id")
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+
+        let production = match sqlx::query(&format!(
+            "INSERT INTO products AS p (name, description, price_amount, currency, seller_id) \
+            VALUES ($1, $2, $3, $4, $5) RETURNING {}",
+            PRODUCT_COLUMNS
+        ))
             .bind(new_productions.name)
-            .bind(new_productions.price)
+            .bind(new_productions.description)
+            .bind(new_productions.price.amount)
+            .bind(new_productions.price.currency)
             .bind(account_id.0)
-            .map(|row: PgRow| Products {
-                id: ProductId(row.get("id")),
-                name: row.get("name"),
-                price: row.get("price"),
-            })
-            .fetch_one(&self.connection)
+            .map(product_from_row)
+            .fetch_one(&mut *tx)
             .await {
-            Ok(production) => Ok(production),
+            Ok(production) => production,
             Err(error) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", error);
-                Err(Error::DatabaseQueryError(error))
+                return Err(Error::DatabaseQueryError(error));
             }
-        }
+        };
+
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(account_id, AuditAction::ProductCreate, production.id.0)
+                .after(Some(&production)),
+        ).await?;
+        notify(&mut tx, &self.changes, StoreChange::product(ProductEventKind::Created, production.id.0, production.seller_id.as_ref())).await?;
+        commit(tx).await?;
+        self.product_cache.invalidate_product(production.id.0);
+        Ok(production)
     }
 
-    ///Update a product information
+    ///Update a product information, fields left to `None` keep their current value.
+    ///The update only goes through when `if_match` matches the current version of the product,
+    ///reviews posted meanwhile don't count as changes
     pub async fn update_product(
         self,
-        product: Products,
+        product: UpdateProduct,
         id: i32,
-        seller_id: i32
+        seller_id: i32,
+        if_match: IfMatch
     ) -> Result<Products, Error> {
-        match sqlx::query("UPDATE products SET name = $1, price = $2\
-        WHERE id = $3 AND seller_id = $4 RETURNING id, name, price")
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+        let before = match lock_product(&mut tx, id).await? {
+            Some(product) => product,
+            None => return Err(Error::NotFound),
+        };
+        if !if_match.matches_version(before.version) {
+            return Err(Error::PreconditionFailed);
+        }
+
+        let after = match sqlx::query(&format!(
+            "UPDATE products AS p SET name = COALESCE($1, name), \
+            description = COALESCE($2, description), price_amount = COALESCE($3, price_amount), \
+            currency = COALESCE($4, currency), updated_on = NOW(), version = version + 1 \
+            WHERE id = $5 AND seller_id = $6 AND deleted_at IS NULL RETURNING {}",
+            PRODUCT_COLUMNS
+        ))
             .bind(product.name)
-            .bind(product.price)
+            .bind(product.description)
+            .bind(product.price.as_ref().map(|price| price.amount))
+            .bind(product.price.and_then(|price| price.currency))
             .bind(id)
             .bind(seller_id)
-            .map(|row: PgRow| Products {
-                id: ProductId(row.get("id")),
-                name: row.get("name"),
-                price: row.get("price"),
-            })
-            .fetch_one(&self.connection)
+            .map(product_from_row)
+            .fetch_optional(&mut *tx)
             .await {
-            Ok(product) => Ok(product),
+            Ok(Some(product)) => product,
+            Ok(None) => return Err(Error::NotFound),
             Err(error) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", error);
-                Err(Error::DatabaseQueryError(error))
+                return Err(Error::DatabaseQueryError(error));
             }
-        }
-    }
+        };
 
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(AccountId(seller_id), AuditAction::ProductUpdate, id)
+                .before(Some(&before))
+                .after(Some(&after)),
+        ).await?;
+        notify(&mut tx, &self.changes, StoreChange::product(ProductEventKind::Updated, id, after.seller_id.as_ref())).await?;
+        commit(tx).await?;
+        self.product_cache.invalidate_product(id);
+        Ok(after)
+    }
 
-    ///Delete a product in database
+    ///Soft delete a product, it stays in database but is hidden until restored
     pub async fn delete_product(
         self,
         id: i32,
         seller_id: AccountId
     ) -> Result<bool, Error> {
-        match sqlx::query("DELETE FROM products WHERE id = $1 AND seller_id = $2")
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+        let before = lock_product(&mut tx, id).await?;
+
+        match sqlx::query("UPDATE products SET deleted_at = NOW(), version = version + 1 \
+        WHERE id = $1 AND seller_id = $2 AND deleted_at IS NULL")
             .bind(id)
             .bind(seller_id.0)
-            .execute(&self.connection)
+            .execute(&mut *tx)
             .await {
-            Ok(_) => Ok(true),
+            Ok(result) if result.rows_affected() == 0 => return Err(Error::NotFound),
+            Ok(_) => (),
             Err(error) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", error);
-                Err(Error::DatabaseQueryError(error))
+                return Err(Error::DatabaseQueryError(error));
             }
         }
+
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(seller_id.clone(), AuditAction::ProductDelete, id)
+                .before(before.as_ref()),
+        ).await?;
+        notify(&mut tx, &self.changes, StoreChange::product(ProductEventKind::Deleted, id, Some(&seller_id))).await?;
+        commit(tx).await?;
+        self.product_cache.invalidate_product(id);
+        Ok(true)
     }
 
+    ///Bring back a soft deleted product
+    pub async fn restore_product(
+        self,
+        id: i32,
+        seller_id: AccountId
+    ) -> Result<Products, Error> {
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
 
-    ///Verify that a user is product owner or not, so they can change product information in database
+        let product = match sqlx::query(&format!(
+            "UPDATE products AS p SET deleted_at = NULL, updated_on = NOW(), version = version + 1 \
+            WHERE id = $1 AND seller_id = $2 AND deleted_at IS NOT NULL RETURNING {}",
+            PRODUCT_COLUMNS
+        ))
+            .bind(id)
+            .bind(seller_id.0)
+            .map(product_from_row)
+            .fetch_optional(&mut *tx)
+            .await {
+            Ok(Some(product)) => product,
+            Ok(None) => return Err(Error::NotFound),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                return Err(Error::DatabaseQueryError(error));
+            }
+        };
+
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(seller_id, AuditAction::ProductRestore, id)
+                .after(Some(&product)),
+        ).await?;
+        notify(&mut tx, &self.changes, StoreChange::product(ProductEventKind::Created, id, product.seller_id.as_ref())).await?;
+        commit(tx).await?;
+        self.product_cache.invalidate_product(id);
+        Ok(product)
+    }
+
+    ///Remove a product from database for good, whether it was soft deleted or not, returning what it was
+    pub async fn purge_product(
+        self,
+        id: i32,
+        admin_id: AccountId
+    ) -> Result<Products, Error> {
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+        let before = match lock_product(&mut tx, id).await? {
+            Some(product) => product,
+            None => return Err(Error::NotFound),
+        };
+
+        if let Err(error) = sqlx::query("DELETE FROM products WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await {
+            tracing::event!(tracing::Level::ERROR, "{:?}", error);
+            return Err(Error::DatabaseQueryError(error));
+        }
+
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(admin_id, AuditAction::ProductPurge, id)
+                .before(Some(&before)),
+        ).await?;
+        notify(&mut tx, &self.changes, StoreChange::product(ProductEventKind::Deleted, id, before.seller_id.as_ref())).await?;
+        commit(tx).await?;
+        self.product_cache.invalidate_product(id);
+        Ok(before)
+    }
+
+    ///Verify that a user is product owner or not, so they can change product information in database.
+    ///Soft deleted products still have an owner, who is the only one able to restore them
     pub async fn is_product_owner(
         &self,
         product_id: i32,
@@ -206,4 +572,283 @@ impl Store {
             }
         }
     }
-}
\ No newline at end of file
+
+    ///Check whether a product exists, soft deleted ones included when `include_deleted`
+    pub async fn product_exists(
+        &self,
+        product_id: i32,
+        include_deleted: bool
+    ) -> Result<bool, Error> {
+        match sqlx::query("SELECT 1 FROM products WHERE id = $1 AND ($2 OR deleted_at IS NULL)")
+            .bind(product_id)
+            .bind(include_deleted)
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(product) => Ok(product.is_some()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    ///Check whether an account has the admin role
+    pub async fn is_admin(
+        &self,
+        account_id: &AccountId
+    ) -> Result<bool, Error> {
+        match sqlx::query("SELECT role FROM accounts WHERE id = $1")
+            .bind(account_id.0)
+            .map(|row: PgRow| row.get::<String, _>("role"))
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(role) => Ok(role.as_deref() == Some(ADMIN_ROLE)),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    ///Get every category, the tree is rebuilt by clients through `parent_id`
+    pub async fn get_categories(self) -> Result<Vec<Category>, Error> {
+        match sqlx::query("SELECT id, name, parent_id FROM categories ORDER BY id")
+            .map(category_from_row)
+            .fetch_all(&self.connection)
+            .await {
+            Ok(categories) => Ok(categories),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    ///Add a new category to database
+    pub async fn add_category(
+        self,
+        new_category: NewCategory,
+        admin_id: AccountId
+    ) -> Result<Category, Error> {
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+
+        let category = match sqlx::query("INSERT INTO categories (name, parent_id) VALUES ($1, $2) RETURNING id, name, parent_id")
+            .bind(new_category.name)
+            .bind(new_category.parent_id.map(|parent| parent.0))
+            .map(category_from_row)
+            .fetch_one(&mut *tx)
+            .await {
+            Ok(category) => category,
+            Err(error) => return Err(category_error(error)),
+        };
+
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(admin_id, AuditAction::CategoryCreate, category.id.0)
+                .after(Some(&category)),
+        ).await?;
+        notify(&mut tx, &self.changes, StoreChange::Categories).await?;
+        commit(tx).await.map(|_| category)
+    }
+
+    ///Rename or move a category, refusing to move it below one of its own descendants
+    pub async fn update_category(
+        self,
+        id: i32,
+        category: NewCategory,
+        admin_id: AccountId
+    ) -> Result<Category, Error> {
+        if let Some(parent) = &category.parent_id {
+            if self.is_category_descendant(parent.0, id).await? {
+                return Err(Error::CategoryCycle);
+            }
+        }
+
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+        let before = sqlx::query("SELECT id, name, parent_id FROM categories WHERE id = $1 FOR UPDATE")
+            .bind(id)
+            .map(category_from_row)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+
+        let after = match sqlx::query("UPDATE categories SET name = $1, parent_id = $2 \
+        WHERE id = $3 RETURNING id, name, parent_id")
+            .bind(category.name)
+            .bind(category.parent_id.map(|parent| parent.0))
+            .bind(id)
+            .map(category_from_row)
+            .fetch_optional(&mut *tx)
+            .await {
+            Ok(Some(category)) => category,
+            Ok(None) => return Err(Error::NotFound),
+            Err(error) => return Err(category_error(error)),
+        };
+
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(admin_id, AuditAction::CategoryUpdate, id)
+                .before(before.as_ref())
+                .after(Some(&after)),
+        ).await?;
+        notify(&mut tx, &self.changes, StoreChange::Categories).await?;
+        commit(tx).await?;
+        //A moved category changes which products the category filter returns
+        self.product_cache.clear();
+        Ok(after)
+    }
+
+    ///Delete a category, products in it are left without category.
+    ///A category with subcategories is kept, they have to be moved or deleted first
+    pub async fn delete_category(
+        self,
+        id: i32,
+        admin_id: AccountId
+    ) -> Result<bool, Error> {
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+
+        let children = sqlx::query("SELECT 1 FROM categories WHERE parent_id = $1 LIMIT 1 FOR UPDATE")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+        if children.is_some() {
+            return Err(Error::CategoryNotEmpty);
+        }
+
+        let before = match sqlx::query("DELETE FROM categories WHERE id = $1 RETURNING id, name, parent_id")
+            .bind(id)
+            .map(category_from_row)
+            .fetch_optional(&mut *tx)
+            .await {
+            Ok(Some(category)) => category,
+            Ok(None) => return Err(Error::NotFound),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                return Err(Error::DatabaseQueryError(error));
+            }
+        };
+
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(admin_id, AuditAction::CategoryDelete, id)
+                .before(Some(&before)),
+        ).await?;
+        notify(&mut tx, &self.changes, StoreChange::Categories).await?;
+        commit(tx).await?;
+        //The products of the category were left without one
+        self.product_cache.clear();
+        Ok(true)
+    }
+
+    ///Check whether `category_id` is `ancestor_id` itself or lies somewhere below it
+    async fn is_category_descendant(
+        &self,
+        category_id: i32,
+        ancestor_id: i32
+    ) -> Result<bool, Error> {
+        match sqlx::query("WITH RECURSIVE subtree AS (\
+            SELECT id FROM categories WHERE id = $1 \
+            UNION ALL SELECT c.id FROM categories c JOIN subtree s ON c.parent_id = s.id) \
+            SELECT 1 FROM subtree WHERE id = $2")
+            .bind(ancestor_id)
+            .bind(category_id)
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(row) => Ok(row.is_some()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    ///Assign a product to a category, or detach it with `None`
+    pub async fn set_product_category(
+        self,
+        id: i32,
+        category_id: Option<CategoryId>,
+        seller_id: AccountId
+    ) -> Result<Products, Error> {
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+        let before = lock_product(&mut tx, id).await?;
+
+        let after = match sqlx::query(&format!(
+            "UPDATE products AS p SET category_id = $1, updated_on = NOW(), version = version + 1 \
+            WHERE id = $2 AND seller_id = $3 AND deleted_at IS NULL RETURNING {}",
+            PRODUCT_COLUMNS
+        ))
+            .bind(category_id.map(|category| category.0))
+            .bind(id)
+            .bind(seller_id.0)
+            .map(product_from_row)
+            .fetch_optional(&mut *tx)
+            .await {
+            Ok(Some(product)) => product,
+            Ok(None) => return Err(Error::NotFound),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                return Err(Error::DatabaseQueryError(error));
+            }
+        };
+
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(seller_id, AuditAction::ProductSetCategory, id)
+                .before(before.as_ref())
+                .after(Some(&after)),
+        ).await?;
+        notify(&mut tx, &self.changes, StoreChange::product(ProductEventKind::Updated, id, after.seller_id.as_ref())).await?;
+        commit(tx).await?;
+        self.product_cache.invalidate_product(id);
+        Ok(after)
+    }
+
+    ///Replace the tags of a product, creating the tags which don't exist yet
+    pub async fn set_product_tags(
+        self,
+        id: i32,
+        tags: Vec<String>,
+        seller_id: AccountId
+    ) -> Result<Products, Error> {
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+        let before = match lock_product(&mut tx, id).await? {
+            Some(product) => product,
+            None => return Err(Error::NotFound),
+        };
+
+        let updated = sqlx::query("UPDATE products SET updated_on = NOW(), version = version + 1 \
+            WHERE id = $1 AND deleted_at IS NULL")
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+        if updated.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+
+        replace_product_tags(&mut tx, id, &tags).await?;
+
+        let after = sqlx::query(&format!("SELECT {} FROM products p WHERE p.id = $1", PRODUCT_COLUMNS))
+            .bind(id)
+            .map(product_from_row)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(seller_id, AuditAction::ProductSetTags, id)
+                .before(Some(&before))
+                .after(Some(&after)),
+        ).await?;
+        notify(&mut tx, &self.changes, StoreChange::product(ProductEventKind::Updated, id, after.seller_id.as_ref())).await?;
+        commit(tx).await?;
+        self.product_cache.invalidate_product(id);
+        Ok(after)
+    }
+
+}
@@ -0,0 +1,189 @@
+use sqlx::{PgConnection, Row};
+
+use handle_errors::Error;
+
+use crate::store::{account_record_from_row, commit, notify, record_event, Store};
+use crate::types::account_tokens::{generate_token, hash_token, TokenPurpose};
+use crate::types::accounts::{normalize_email, Account, AccountId, AccountRecord};
+use crate::types::audit::{AuditAction, NewAuditEvent};
+use crate::types::events::StoreChange;
+
+/// Mark a token as used and return its account, tokens are single use
+pub(crate) async fn consume_token(
+    connection: &mut PgConnection,
+    token: &str,
+    purpose: TokenPurpose
+) -> Result<AccountId, Error> {
+    match sqlx::query("UPDATE account_tokens SET used_on = NOW() \
+        WHERE token_hash = $1 AND purpose = $2 AND used_on IS NULL AND expires_on > NOW() \
+        RETURNING account_id")
+        .bind(hash_token(token))
+        .bind(purpose.as_str())
+        .fetch_optional(connection)
+        .await
+    {
+        Ok(Some(row)) => Ok(AccountId(row.get("account_id"))),
+        Ok(None) => Err(Error::InvalidToken),
+        Err(e) => {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            Err(Error::DatabaseQueryError(e))
+        }
+    }
+}
+
+/// Issue a token in the transaction of `connection`, earlier unused tokens of the same purpose stop working
+pub(crate) async fn issue_token(
+    connection: &mut PgConnection,
+    account_id: &AccountId,
+    purpose: TokenPurpose
+) -> Result<String, Error> {
+    let token = generate_token();
+
+    sqlx::query("UPDATE account_tokens SET used_on = NOW() \
+        WHERE account_id = $1 AND purpose = $2 AND used_on IS NULL")
+        .bind(account_id.0)
+        .bind(purpose.as_str())
+        .execute(&mut *connection)
+        .await
+        .map_err(Error::DatabaseQueryError)?;
+
+    match sqlx::query("INSERT INTO account_tokens (account_id, purpose, token_hash, expires_on) \
+        VALUES ($1, $2, $3, NOW() + make_interval(mins => $4))")
+        .bind(account_id.0)
+        .bind(purpose.as_str())
+        .bind(hash_token(&token))
+        .bind(purpose.ttl_minutes())
+        .execute(connection)
+        .await
+    {
+        Ok(_) => Ok(token),
+        Err(e) => {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            Err(Error::DatabaseQueryError(e))
+        }
+    }
+}
+
+impl Store {
+    ///Issue a token to be sent by mail or handed out, earlier unused tokens of the same purpose stop working
+    pub async fn create_account_token(
+        &self,
+        account_id: &AccountId,
+        purpose: TokenPurpose
+    ) -> Result<String, Error> {
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+        let token = issue_token(&mut tx, account_id, purpose).await?;
+        commit(tx).await?;
+        Ok(token)
+    }
+
+    ///Get an account by id
+    pub async fn get_account_by_id(&self, account_id: &AccountId) -> Result<Account, Error> {
+        self.get_account_record_by_id(account_id).await.map(Account::from)
+    }
+
+    ///Get an account along its password hash, to check the password of a logged in account
+    pub async fn get_account_record_by_id(&self, account_id: &AccountId) -> Result<AccountRecord, Error> {
+        match sqlx::query("SELECT * FROM accounts WHERE id = $1")
+            .bind(account_id.0)
+            .map(account_record_from_row)
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(Some(record)) => Ok(record),
+            Ok(None) => Err(Error::NotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    ///Find the account registered with an email address
+    pub async fn get_account_by_email(&self, email: &str) -> Result<Option<Account>, Error> {
+        match sqlx::query("SELECT * FROM accounts WHERE email = $1")
+            .bind(normalize_email(email))
+            .map(account_record_from_row)
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(record) => Ok(record.map(Account::from)),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    ///Check whether an account verified its email address, which it needs to sell
+    pub async fn is_email_verified(&self, account_id: &AccountId) -> Result<bool, Error> {
+        match sqlx::query("SELECT email_verified_on IS NOT NULL AS verified FROM accounts WHERE id = $1")
+            .bind(account_id.0)
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(row) => Ok(row.map(|row| row.get("verified")).unwrap_or(false)),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    ///Verify the email address of the account a verification token was sent to
+    pub async fn verify_email(self, token: String) -> Result<Account, Error> {
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+        let account_id = consume_token(&mut tx, &token, TokenPurpose::VerifyEmail).await?;
+
+        let account: Account = match sqlx::query("UPDATE accounts \
+            SET email_verified_on = COALESCE(email_verified_on, NOW()) WHERE id = $1 RETURNING *")
+            .bind(account_id.0)
+            .map(account_record_from_row)
+            .fetch_one(&mut *tx)
+            .await
+        {
+            Ok(record) => record.into(),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(account_id.clone(), AuditAction::AccountVerifyEmail, account_id.0)
+                .after(Some(&account)),
+        ).await?;
+        notify(&mut tx, &self.changes, StoreChange::account(&account_id, AuditAction::AccountVerifyEmail)).await?;
+        commit(tx).await?;
+        Ok(account)
+    }
+
+    ///Replace the password of the account a reset token was sent to.
+    ///Receiving the token proves the ownership of the address, which is verified along
+    pub async fn reset_password(self, token: String, password_hash: String) -> Result<bool, Error> {
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+        let account_id = consume_token(&mut tx, &token, TokenPurpose::ResetPassword).await?;
+
+        match sqlx::query("UPDATE accounts SET password = $1, \
+            email_verified_on = COALESCE(email_verified_on, NOW()) WHERE id = $2")
+            .bind(password_hash)
+            .bind(account_id.0)
+            .execute(&mut *tx)
+            .await
+        {
+            Ok(_) => (),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        }
+
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(account_id.clone(), AuditAction::AccountResetPassword, account_id.0),
+        ).await?;
+        notify(&mut tx, &self.changes, StoreChange::account(&account_id, AuditAction::AccountResetPassword)).await?;
+        commit(tx).await.map(|_| true)
+    }
+}
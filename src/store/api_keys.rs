@@ -0,0 +1,168 @@
+use chrono::prelude::*;
+use sqlx::{postgres::PgRow, Row};
+
+use handle_errors::Error;
+
+use crate::store::{commit, notify, record_event, Store};
+use crate::types::account_tokens::hash_token;
+use crate::types::accounts::{AccountId, Session};
+use crate::types::api_keys::{
+    generate_api_key, ApiKey, ApiKeyId, ApiScope, CreatedApiKey, NewApiKey, API_KEY_DISPLAY_LENGTH,
+};
+use crate::types::audit::{AuditAction, NewAuditEvent};
+use crate::types::events::StoreChange;
+
+/// Requests made with a key within this long of its `last_used_on` don't write it again
+const LAST_USED_RESOLUTION: std::time::Duration = std::time::Duration::from_secs(60);
+
+const API_KEY_COLUMNS: &str = "id, name, prefix, scopes, expires_on, last_used_on, revoked_on, created_on";
+
+fn parse_scopes(scopes: Vec<String>) -> Vec<ApiScope> {
+    scopes.iter().filter_map(|scope| ApiScope::parse(scope)).collect()
+}
+
+fn api_key_from_row(row: PgRow) -> ApiKey {
+    ApiKey {
+        id: ApiKeyId(row.get("id")),
+        name: row.get("name"),
+        prefix: row.get("prefix"),
+        scopes: parse_scopes(row.get("scopes")),
+        expires_on: row.get("expires_on"),
+        last_used_on: row.get("last_used_on"),
+        revoked_on: row.get("revoked_on"),
+        created_on: row.get("created_on"),
+    }
+}
+
+impl Store {
+    ///Create an API key for an account, the key is returned this once and only its hash is stored
+    pub async fn create_api_key(
+        self,
+        account_id: &AccountId,
+        new_key: NewApiKey
+    ) -> Result<CreatedApiKey, Error> {
+        let key = generate_api_key();
+        let scopes: Vec<&str> = new_key.scopes.iter().map(ApiScope::as_str).collect();
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+
+        let api_key = match sqlx::query(&format!(
+            "INSERT INTO api_keys (account_id, name, prefix, key_hash, scopes, expires_on) \
+            VALUES ($1, $2, $3, $4, $5, $6) RETURNING {}", API_KEY_COLUMNS
+        ))
+            .bind(account_id.0)
+            .bind(new_key.name.trim())
+            .bind(&key[..API_KEY_DISPLAY_LENGTH])
+            .bind(hash_token(&key))
+            .bind(&scopes)
+            .bind(new_key.expires_on)
+            .map(api_key_from_row)
+            .fetch_one(&mut *tx)
+            .await
+        {
+            Ok(api_key) => api_key,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(account_id.clone(), AuditAction::ApiKeyCreate, api_key.id.0)
+                .after(Some(&api_key)),
+        ).await?;
+        notify(&mut tx, &self.changes, StoreChange::account(account_id, AuditAction::ApiKeyCreate)).await?;
+        commit(tx).await?;
+        Ok(CreatedApiKey { api_key, key })
+    }
+
+    ///List the API keys of an account, revoked and expired ones included
+    pub async fn get_api_keys(self, account_id: &AccountId) -> Result<Vec<ApiKey>, Error> {
+        match sqlx::query(&format!(
+            "SELECT {} FROM api_keys WHERE account_id = $1 ORDER BY id DESC", API_KEY_COLUMNS
+        ))
+            .bind(account_id.0)
+            .map(api_key_from_row)
+            .fetch_all(&self.connection)
+            .await
+        {
+            Ok(api_keys) => Ok(api_keys),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    ///Revoke an API key of an account, requests made with it are refused from then on
+    pub async fn revoke_api_key(self, account_id: &AccountId, id: i32) -> Result<ApiKey, Error> {
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+
+        let before = sqlx::query(&format!(
+            "SELECT {} FROM api_keys WHERE id = $1 AND account_id = $2 FOR UPDATE", API_KEY_COLUMNS
+        ))
+            .bind(id)
+            .bind(account_id.0)
+            .map(api_key_from_row)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(Error::DatabaseQueryError)?
+            .ok_or(Error::NotFound)?;
+
+        let after = match sqlx::query(&format!(
+            "UPDATE api_keys SET revoked_on = COALESCE(revoked_on, NOW()) WHERE id = $1 RETURNING {}",
+            API_KEY_COLUMNS
+        ))
+            .bind(id)
+            .map(api_key_from_row)
+            .fetch_one(&mut *tx)
+            .await
+        {
+            Ok(api_key) => api_key,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(account_id.clone(), AuditAction::ApiKeyRevoke, id)
+                .before(Some(&before))
+                .after(Some(&after)),
+        ).await?;
+        notify(&mut tx, &self.changes, StoreChange::account(account_id, AuditAction::ApiKeyRevoke)).await?;
+        commit(tx).await?;
+        Ok(after)
+    }
+
+    ///Find the session of a valid API key along its scopes, and note when it was last used.
+    ///`last_used_on` is only written once per `LAST_USED_RESOLUTION`, not on every request
+    pub async fn authenticate_api_key(&self, key: &str) -> Result<Option<(Session, Vec<ApiScope>)>, Error> {
+        match sqlx::query("WITH valid AS (SELECT id, account_id, scopes, expires_on, last_used_on FROM api_keys \
+            WHERE key_hash = $1 AND revoked_on IS NULL AND (expires_on IS NULL OR expires_on > NOW())), \
+            touched AS (UPDATE api_keys SET last_used_on = NOW() WHERE id IN (SELECT id FROM valid \
+            WHERE last_used_on IS NULL OR last_used_on < NOW() - make_interval(secs => $2))) \
+            SELECT id, account_id, scopes, expires_on FROM valid")
+            .bind(hash_token(key))
+            .bind(LAST_USED_RESOLUTION.as_secs_f64())
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(row) => Ok(row.map(|row| {
+                let session = Session {
+                    exp: row
+                        .get::<Option<DateTime<Utc>>, _>("expires_on")
+                        .unwrap_or(DateTime::<Utc>::MAX_UTC),
+                    account_id: AccountId(row.get("account_id")),
+                    api_key_id: Some(ApiKeyId(row.get("id"))),
+                };
+                (session, parse_scopes(row.get("scopes")))
+            })),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use sqlx::{
+    postgres::PgRow,
+    PgConnection, Postgres, QueryBuilder, Row,
+};
+
+use handle_errors::Error;
+
+use crate::store::Store;
+use crate::types::accounts::AccountId;
+use crate::types::audit::{AuditEvent, AuditFilter, NewAuditEvent};
+
+/// Write an audit event, meant to run in the transaction of the mutation it describes
+pub(crate) async fn record_event(
+    connection: &mut PgConnection,
+    event: NewAuditEvent
+) -> Result<(), Error> {
+    match sqlx::query("INSERT INTO audit_events (actor_id, action, target_type, target_id, before, after) \
+        VALUES ($1, $2, $3, $4, $5, $6)")
+        .bind(event.actor_id.0)
+        .bind(event.action.as_str())
+        .bind(event.action.target_type())
+        .bind(event.target_id)
+        .bind(event.before)
+        .bind(event.after)
+        .execute(connection)
+        .await {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            tracing::event!(tracing::Level::ERROR, "{:?}", error);
+            Err(Error::DatabaseQueryError(error))
+        }
+    }
+}
+
+impl Store {
+    ///Get a page of the audit log, newest events first
+    pub async fn get_audit_events(
+        self,
+        limit: Option<i32>,
+        offset: i32,
+        filter: AuditFilter
+    ) -> Result<Vec<AuditEvent>, Error> {
+        let mut query = QueryBuilder::<Postgres>::new(
+            "SELECT id, actor_id, action, target_type, target_id, before, after, created_on \
+            FROM audit_events WHERE TRUE"
+        );
+        if let Some(actor_id) = filter.actor_id {
+            query.push(" AND actor_id = ").push_bind(actor_id);
+        }
+        if let Some(action) = filter.action {
+            query.push(" AND action = ").push_bind(action);
+        }
+        if let Some(target_type) = filter.target_type {
+            query.push(" AND target_type = ").push_bind(target_type);
+        }
+        if let Some(target_id) = filter.target_id {
+            query.push(" AND target_id = ").push_bind(target_id);
+        }
+        query
+            .push(" ORDER BY id DESC LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+
+        match query
+            .build()
+            .map(|row: PgRow| AuditEvent {
+                id: row.get("id"),
+                actor_id: row.get::<Option<i32>, _>("actor_id").map(AccountId),
+                action: row.get("action"),
+                target_type: row.get("target_type"),
+                target_id: row.get("target_id"),
+                before: row.get("before"),
+                after: row.get("after"),
+                created_on: row.get("created_on"),
+            })
+            .fetch_all(&self.connection)
+            .await {
+            Ok(events) => Ok(events),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::types::cache::CacheStats;
+use crate::types::products::{ProductFilter, Products};
+
+/// Parameters of `GET /products`, the key of a cached product list
+type ListKey = (Option<i32>, i32, ProductFilter);
+
+struct Entry<V> {
+    value: V,
+    inserted_on: Instant,
+}
+
+/// Map whose entries expire after `ttl`, the oldest entry makes room once `capacity` is reached
+struct TtlMap<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> TtlMap<K, V> {
+    fn new(ttl: Duration, capacity: usize) -> Self {
+        TtlMap { entries: HashMap::new(), ttl, capacity }
+    }
+
+    fn get(&mut self, key: &K) -> Option<V> {
+        match self.entries.get(key) {
+            Some(entry) if entry.inserted_on.elapsed() < self.ttl => Some(entry.value.clone()),
+            Some(_) => {
+                self.entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let ttl = self.ttl;
+            self.entries.retain(|_, entry| entry.inserted_on.elapsed() < ttl);
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.inserted_on)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, Entry { value, inserted_on: Instant::now() });
+    }
+}
+
+struct Maps {
+    lists: TtlMap<ListKey, Vec<Products>>,
+    products: TtlMap<i32, Products>,
+    /// Bumped by every invalidation, a lookup started before one doesn't fill the cache
+    generation: u64,
+}
+
+/// Read cache of the product lists and product details, shared by the clones of the store.
+/// A disabled cache never stores anything and counts nothing
+#[derive(Clone)]
+pub struct ProductCache {
+    maps: Option<Arc<Mutex<Maps>>>,
+    ttl: Duration,
+    capacity: usize,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+}
+
+impl std::fmt::Debug for ProductCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProductCache")
+            .field("enabled", &self.maps.is_some())
+            .field("ttl", &self.ttl)
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl ProductCache {
+    /// A cache holding at most `capacity` lists and as many products, each for `ttl`
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        ProductCache {
+            maps: Some(Arc::new(Mutex::new(Maps {
+                lists: TtlMap::new(ttl, capacity),
+                products: TtlMap::new(ttl, capacity),
+                generation: 0,
+            }))),
+            ttl,
+            capacity,
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn disabled() -> Self {
+        ProductCache {
+            maps: None,
+            ttl: Duration::ZERO,
+            capacity: 0,
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        if config.product_cache {
+            ProductCache::new(
+                Duration::from_secs(config.product_cache_ttl),
+                config.product_cache_capacity,
+            )
+        } else {
+            ProductCache::disabled()
+        }
+    }
+
+    fn lock(&self) -> Option<std::sync::MutexGuard<'_, Maps>> {
+        // A panic while holding the lock leaves the maps consistent, the lock is taken back
+        self.maps
+            .as_ref()
+            .map(|maps| maps.lock().unwrap_or_else(|poisoned| poisoned.into_inner()))
+    }
+
+    fn count(&self, hit: bool) {
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The cached list, or the generation to hand back to `put_list` after reading the database
+    pub(crate) fn get_list(&self, key: &ListKey) -> Result<Vec<Products>, Option<u64>> {
+        let Some(mut maps) = self.lock() else { return Err(None) };
+        let cached = maps.lists.get(key);
+        self.count(cached.is_some());
+        cached.ok_or(Some(maps.generation))
+    }
+
+    pub(crate) fn put_list(&self, generation: Option<u64>, key: ListKey, products: Vec<Products>) {
+        if let (Some(mut maps), Some(generation)) = (self.lock(), generation) {
+            if maps.generation == generation {
+                maps.lists.insert(key, products);
+            }
+        }
+    }
+
+    /// The cached product, or the generation to hand back to `put_product` after reading the database
+    pub(crate) fn get_product(&self, id: i32) -> Result<Products, Option<u64>> {
+        let Some(mut maps) = self.lock() else { return Err(None) };
+        let cached = maps.products.get(&id);
+        self.count(cached.is_some());
+        cached.ok_or(Some(maps.generation))
+    }
+
+    pub(crate) fn put_product(&self, generation: Option<u64>, product: Products) {
+        if let (Some(mut maps), Some(generation)) = (self.lock(), generation) {
+            if maps.generation == generation {
+                maps.products.insert(product.id.0, product);
+            }
+        }
+    }
+
+    /// Forget a changed product and every list, as any of them may hold it
+    pub fn invalidate_product(&self, id: i32) {
+        if let Some(mut maps) = self.lock() {
+            maps.generation += 1;
+            maps.products.entries.remove(&id);
+            maps.lists.entries.clear();
+        }
+    }
+
+    /// Forget everything, after changes spanning many products
+    pub fn clear(&self) {
+        if let Some(mut maps) = self.lock() {
+            maps.generation += 1;
+            maps.products.entries.clear();
+            maps.lists.entries.clear();
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let entries = self
+            .lock()
+            .map(|maps| maps.lists.entries.len() + maps.products.entries.len())
+            .unwrap_or_default();
+        CacheStats {
+            enabled: self.maps.is_some(),
+            entries,
+            capacity: self.capacity * 2,
+            ttl_seconds: self.ttl.as_secs(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod cache_test {
+    use super::{Duration, ProductCache, ProductFilter, TtlMap};
+
+    #[test]
+    fn oldest_entry_makes_room() {
+        let mut map = TtlMap::new(Duration::from_secs(60), 2);
+        map.insert(1, "a");
+        map.insert(2, "b");
+        map.insert(3, "c");
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.get(&3), Some("c"));
+        assert_eq!(map.entries.len(), 2);
+    }
+
+    #[test]
+    fn entries_expire() {
+        let mut map = TtlMap::new(Duration::ZERO, 2);
+        map.insert(1, "a");
+        assert_eq!(map.get(&1), None);
+    }
+
+    #[test]
+    fn invalidation_wins_over_slow_reads() {
+        let cache = ProductCache::new(Duration::from_secs(60), 10);
+        let key = (None, 0, ProductFilter::default());
+
+        let generation = cache.get_list(&key).unwrap_err();
+        cache.invalidate_product(1);
+        cache.put_list(generation, key.clone(), vec![]);
+        assert!(cache.get_list(&key).is_err());
+
+        let generation = cache.get_list(&key).unwrap_err();
+        cache.put_list(generation, key.clone(), vec![]);
+        assert_eq!(cache.get_list(&key).unwrap().len(), 0);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 3, 1));
+    }
+
+    #[test]
+    fn disabled_cache_stores_nothing() {
+        let cache = ProductCache::disabled();
+        let key = (None, 0, ProductFilter::default());
+        assert_eq!(cache.get_list(&key).unwrap_err(), None);
+        cache.put_list(None, key.clone(), vec![]);
+        assert!(cache.get_list(&key).is_err());
+        assert_eq!(cache.stats().misses, 0);
+    }
+}
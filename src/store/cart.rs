@@ -0,0 +1,180 @@
+use sqlx::{postgres::PgRow, PgConnection, Row};
+
+use handle_errors::Error;
+
+use crate::store::{commit, Store};
+use crate::types::accounts::AccountId;
+use crate::types::cart::{Cart, CartItem};
+use crate::types::money::Money;
+use crate::types::products::ProductId;
+
+/// Items of a cart joined with the current state of their product, `c` being the alias of `cart_items`
+const CART_ITEMS_QUERY: &str = "SELECT c.product_id, p.name, c.quantity, c.unit_price_amount, \
+    c.unit_price_currency, c.added_on, c.updated_on, p.currency, \
+    CASE WHEN p.deleted_at IS NULL THEN p.price_amount END AS current_amount \
+    FROM cart_items c JOIN products p ON p.id = c.product_id \
+    WHERE c.account_id = $1 AND ($2::INT IS NULL OR c.product_id = $2) \
+    ORDER BY c.added_on, c.product_id";
+
+fn cart_item_from_row(row: PgRow) -> CartItem {
+    CartItem {
+        product_id: ProductId(row.get("product_id")),
+        name: row.get("name"),
+        quantity: row.get("quantity"),
+        unit_price: Money {
+            amount: row.get("unit_price_amount"),
+            currency: row.get("unit_price_currency"),
+        },
+        current_price: row
+            .get::<Option<i64>, _>("current_amount")
+            .map(|amount| Money {
+                amount,
+                currency: row.get("currency"),
+            }),
+        added_on: row.get("added_on"),
+        updated_on: row.get("updated_on"),
+    }
+}
+
+/// Read the items of a cart, or a single one of them, locking the rows when `lock` is set
+async fn fetch_cart_items(
+    connection: &mut PgConnection,
+    account_id: &AccountId,
+    product_id: Option<i32>,
+    lock: bool
+) -> Result<Vec<CartItem>, Error> {
+    let query = if lock {
+        format!("{} FOR UPDATE OF c", CART_ITEMS_QUERY)
+    } else {
+        CART_ITEMS_QUERY.to_string()
+    };
+    match sqlx::query(&query)
+        .bind(account_id.0)
+        .bind(product_id)
+        .map(cart_item_from_row)
+        .fetch_all(connection)
+        .await
+    {
+        Ok(items) => Ok(items),
+        Err(e) => {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            Err(Error::DatabaseQueryError(e))
+        }
+    }
+}
+
+impl Store {
+    ///Get the cart of an account, priced with the current product prices
+    pub async fn get_cart(self, account_id: AccountId) -> Result<Cart, Error> {
+        let mut connection = self.connection.acquire().await.map_err(Error::DatabaseQueryError)?;
+        let items = fetch_cart_items(&mut connection, &account_id, None, false).await?;
+        Cart::new(items)
+    }
+
+    ///Put a product in the cart or change its quantity, the price seen now is kept for checkout.
+    ///Products of the account itself cannot be added
+    pub async fn set_cart_item(
+        self,
+        account_id: AccountId,
+        product_id: i32,
+        quantity: i32
+    ) -> Result<CartItem, Error> {
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+
+        let product = sqlx::query("SELECT seller_id, price_amount, currency FROM products \
+            WHERE id = $1 AND deleted_at IS NULL")
+            .bind(product_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(Error::DatabaseQueryError)?
+            .ok_or(Error::NotFound)?;
+        if product.get::<Option<i32>, _>("seller_id") == Some(account_id.0) {
+            return Err(Error::OwnProduct);
+        }
+
+        match sqlx::query("INSERT INTO cart_items \
+            (account_id, product_id, quantity, unit_price_amount, unit_price_currency) \
+            VALUES ($1, $2, $3, $4, $5) \
+            ON CONFLICT (account_id, product_id) DO UPDATE SET quantity = EXCLUDED.quantity, \
+            unit_price_amount = EXCLUDED.unit_price_amount, \
+            unit_price_currency = EXCLUDED.unit_price_currency, updated_on = NOW()")
+            .bind(account_id.0)
+            .bind(product_id)
+            .bind(quantity)
+            .bind(product.get::<i64, _>("price_amount"))
+            .bind(product.get::<String, _>("currency"))
+            .execute(&mut *tx)
+            .await
+        {
+            Ok(_) => (),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        }
+
+        let item = fetch_cart_items(&mut tx, &account_id, Some(product_id), false)
+            .await?
+            .pop()
+            .ok_or(Error::NotFound)?;
+        commit(tx).await?;
+        Ok(item)
+    }
+
+    ///Take a product out of the cart
+    pub async fn remove_cart_item(
+        self,
+        account_id: AccountId,
+        product_id: i32
+    ) -> Result<bool, Error> {
+        match sqlx::query("DELETE FROM cart_items WHERE account_id = $1 AND product_id = $2")
+            .bind(account_id.0)
+            .bind(product_id)
+            .execute(&self.connection)
+            .await
+        {
+            Ok(result) if result.rows_affected() == 0 => Err(Error::NotFound),
+            Ok(_) => Ok(true),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    ///Check the cart against the current prices before checking out.
+    ///Items whose price changed get the new price and deleted products are dropped,
+    ///the buyer is then asked to review the cart through `Error::CartChanged`
+    pub async fn checkout_cart(self, account_id: AccountId) -> Result<Cart, Error> {
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+        let items = fetch_cart_items(&mut tx, &account_id, None, true).await?;
+        if items.is_empty() {
+            return Err(Error::EmptyCart);
+        }
+
+        let stale: Vec<&CartItem> = items.iter().filter(|item| item.is_stale()).collect();
+        if stale.is_empty() {
+            commit(tx).await?;
+            return Cart::new(items);
+        }
+
+        for item in stale.iter() {
+            let query = match &item.current_price {
+                Some(price) => sqlx::query("UPDATE cart_items SET unit_price_amount = $3, \
+                    unit_price_currency = $4, updated_on = NOW() \
+                    WHERE account_id = $1 AND product_id = $2")
+                    .bind(account_id.0)
+                    .bind(item.product_id.0)
+                    .bind(price.amount)
+                    .bind(&price.currency),
+                None => sqlx::query("DELETE FROM cart_items WHERE account_id = $1 AND product_id = $2")
+                    .bind(account_id.0)
+                    .bind(item.product_id.0),
+            };
+            query.execute(&mut *tx).await.map_err(Error::DatabaseQueryError)?;
+        }
+        commit(tx).await?;
+
+        Err(Error::CartChanged(stale.iter().map(|item| item.product_id.0).collect()))
+    }
+}
@@ -0,0 +1,146 @@
+use futures_util::TryStreamExt;
+use sqlx::{PgConnection, Row};
+use tokio::sync::mpsc::Sender;
+
+use handle_errors::Error;
+
+use crate::store::{
+    commit, lock_product, notify, product_from_row, record_event, replace_product_tags, Store, PRODUCT_COLUMNS,
+};
+use crate::types::accounts::AccountId;
+use crate::types::audit::{AuditAction, NewAuditEvent};
+use crate::types::catalog::{CatalogProduct, ImportRowError};
+use crate::types::events::{ProductEventKind, StoreChange};
+use crate::types::products::Products;
+
+async fn category_exists(connection: &mut PgConnection, id: i32) -> Result<bool, Error> {
+    sqlx::query("SELECT 1 FROM categories WHERE id = $1")
+        .bind(id)
+        .fetch_optional(connection)
+        .await
+        .map(|row| row.is_some())
+        .map_err(Error::DatabaseQueryError)
+}
+
+impl Store {
+    ///Import a whole catalog in a single transaction, which is rolled back on a dry run or when a row
+    ///refers to a product of another seller or to an unknown category. A row with an `id` replaces
+    ///that product, the others create one. Returns the created and updated products along their
+    ///kind of change, which don't exist after a dry run, or the rows in error
+    pub async fn import_products(
+        self,
+        products: Vec<CatalogProduct>,
+        seller_id: AccountId,
+        dry_run: bool
+    ) -> Result<(Vec<(ProductEventKind, Products)>, Vec<ImportRowError>), Error> {
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+        let mut imported = Vec::with_capacity(products.len());
+        let mut errors = Vec::new();
+
+        for (index, row) in products.iter().enumerate() {
+            if let Some(category_id) = row.category_id {
+                if !category_exists(&mut tx, category_id).await? {
+                    errors.push(ImportRowError::unknown(index + 1, "category_id", "is not a category"));
+                    continue;
+                }
+            }
+
+            let before = match row.id {
+                Some(id) => match lock_product(&mut tx, id).await? {
+                    Some(before) if before.seller_id.as_ref() == Some(&seller_id) => Some(before),
+                    _ => {
+                        errors.push(ImportRowError::unknown(index + 1, "id", "is not one of your products"));
+                        continue;
+                    }
+                },
+                None => None,
+            };
+            let query = match before {
+                Some(_) => "UPDATE products SET name = $1, description = $2, price_amount = $3, currency = $4, \
+                    category_id = $6, updated_on = NOW(), version = version + 1 \
+                    WHERE id = $7 AND seller_id = $5 AND deleted_at IS NULL RETURNING id",
+                None => "INSERT INTO products (name, description, price_amount, currency, seller_id, category_id) \
+                    VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+            };
+            let mut query = sqlx::query(query)
+                .bind(&row.product.name)
+                .bind(&row.product.description)
+                .bind(row.product.price.amount)
+                .bind(&row.product.price.currency)
+                .bind(seller_id.0)
+                .bind(row.category_id);
+            if before.is_some() {
+                query = query.bind(row.id);
+            }
+            let id: i32 = match query.fetch_optional(&mut *tx).await {
+                Ok(Some(created)) => created.get("id"),
+                //Soft deleted, restoring comes first
+                Ok(None) => {
+                    errors.push(ImportRowError::unknown(index + 1, "id", "is not one of your products"));
+                    continue;
+                }
+                Err(error) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                    return Err(Error::DatabaseQueryError(error));
+                }
+            };
+            replace_product_tags(&mut tx, id, &row.tags).await?;
+            let after = lock_product(&mut tx, id).await?.ok_or(Error::NotFound)?;
+
+            let (kind, action) = match before {
+                Some(_) => (ProductEventKind::Updated, AuditAction::ProductUpdate),
+                None => (ProductEventKind::Created, AuditAction::ProductCreate),
+            };
+            record_event(
+                &mut tx,
+                NewAuditEvent::new(seller_id.clone(), action, id)
+                    .before(before.as_ref())
+                    .after(Some(&after)),
+            ).await?;
+            imported.push((kind, after));
+        }
+
+        if dry_run || !errors.is_empty() {
+            tx.rollback().await.map_err(Error::DatabaseQueryError)?;
+        } else {
+            notify(&mut tx, &self.changes, StoreChange::Catalog { seller_id: seller_id.0 }).await?;
+            commit(tx).await?;
+            self.product_cache.clear();
+        }
+        match errors.is_empty() {
+            true => Ok((imported, errors)),
+            false => Ok((Vec::new(), errors)),
+        }
+    }
+
+    ///Send every live product of a seller to `sender`, one row at a time.
+    ///Stops early when the receiving side is dropped, e.g. when the client disconnects
+    pub async fn export_products(
+        self,
+        seller_id: AccountId,
+        sender: Sender<Result<Products, Error>>
+    ) {
+        let query = format!(
+            "SELECT {} FROM products p WHERE p.seller_id = $1 AND p.deleted_at IS NULL ORDER BY p.id",
+            PRODUCT_COLUMNS
+        );
+        let mut rows = sqlx::query(&query)
+            .bind(seller_id.0)
+            .fetch(&self.connection);
+
+        loop {
+            let next = match rows.try_next().await {
+                Ok(Some(row)) => Ok(product_from_row(row)),
+                Ok(None) => break,
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    Err(Error::DatabaseQueryError(e))
+                }
+            };
+            let failed = next.is_err();
+            if sender.send(next).await.is_err() || failed {
+                break;
+            }
+        }
+    }
+}
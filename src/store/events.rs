@@ -0,0 +1,107 @@
+use futures_util::{stream, Stream};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::config::Config;
+use crate::types::events::{EventFilter, ProductEvent};
+
+/// Events kept for slow subscribers when the configuration doesn't say otherwise
+pub const DEFAULT_EVENT_BUFFER: usize = 256;
+
+/// What a subscriber reads from the bus
+#[derive(Debug, Clone)]
+pub enum Delivery {
+    Event(Box<ProductEvent>),
+    /// The subscriber fell behind and missed that many events, it should reload what it shows
+    Lagged(u64),
+    /// Changes of other instances were lost along the database connection, every subscriber should reload
+    Resync,
+}
+
+/// Broadcast of the product changes to the open streams, shared by the clones of the store.
+/// The bus keeps the last `capacity` events whatever the number of subscribers, a subscriber
+/// reading slower than the changes come skips the oldest ones and is told how many it missed
+#[derive(Debug, Clone)]
+pub struct ProductEvents {
+    sender: broadcast::Sender<Option<ProductEvent>>,
+}
+
+impl ProductEvents {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        ProductEvents { sender }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        ProductEvents::new(config.event_buffer)
+    }
+
+    /// Hand an event to the current subscribers, it is dropped when nobody listens
+    pub fn publish(&self, event: ProductEvent) {
+        let _ = self.sender.send(Some(event));
+    }
+
+    /// Tell every subscriber to reload, whatever its filter, when changes may have been missed
+    pub fn resync(&self) {
+        let _ = self.sender.send(None);
+    }
+
+    pub fn subscribers(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /// The events matching `filter` from now on. Dropping the stream unsubscribes
+    pub fn subscribe(&self, filter: EventFilter) -> impl Stream<Item = Delivery> + Send + 'static {
+        stream::unfold((self.sender.subscribe(), filter), |(mut receiver, filter)| async move {
+            loop {
+                let delivery = match receiver.recv().await {
+                    Ok(Some(event)) if filter.matches(&event) => Delivery::Event(Box::new(event)),
+                    Ok(Some(_)) => continue,
+                    Ok(None) => Delivery::Resync,
+                    Err(RecvError::Lagged(missed)) => Delivery::Lagged(missed),
+                    Err(RecvError::Closed) => return None,
+                };
+                return Some((delivery, (receiver, filter)));
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod events_test {
+    use super::{Delivery, EventFilter, ProductEvent, ProductEvents};
+    use crate::types::accounts::AccountId;
+    use futures_util::StreamExt;
+
+    #[tokio::test]
+    async fn filtered_delivery() {
+        let events = ProductEvents::new(8);
+        let stream = events.subscribe(EventFilter { seller_id: Some(2), product_id: None });
+        tokio::pin!(stream);
+        assert_eq!(events.subscribers(), 1);
+
+        events.publish(ProductEvent::deleted(1, Some(AccountId(3))));
+        events.publish(ProductEvent::deleted(2, Some(AccountId(2))));
+        match stream.next().await {
+            Some(Delivery::Event(event)) => assert_eq!(event.product_id.0, 2),
+            other => panic!("unexpected delivery {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn slow_subscribers_skip_ahead() {
+        let events = ProductEvents::new(2);
+        let mut stream = Box::pin(events.subscribe(EventFilter::default()));
+
+        for id in 0..5 {
+            events.publish(ProductEvent::deleted(id, None));
+        }
+        assert!(matches!(stream.next().await, Some(Delivery::Lagged(3))));
+        assert!(matches!(stream.next().await, Some(Delivery::Event(event)) if event.product_id.0 == 3));
+        events.resync();
+        assert!(matches!(stream.next().await, Some(Delivery::Event(event)) if event.product_id.0 == 4));
+        assert!(matches!(stream.next().await, Some(Delivery::Resync)));
+
+        drop(stream);
+        assert_eq!(events.subscribers(), 0);
+    }
+}
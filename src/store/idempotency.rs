@@ -0,0 +1,123 @@
+use sqlx::{postgres::PgRow, Row};
+
+use handle_errors::Error;
+
+use crate::store::Store;
+use crate::types::accounts::AccountId;
+use crate::types::idempotency::{
+    IdempotencyState, StoredResponse, IDEMPOTENCY_KEY_TTL_HOURS, IDEMPOTENCY_LEASE_SECONDS,
+};
+
+impl Store {
+    ///Claim an idempotency key for a request, or find out what became of the request which claimed it first.
+    ///Expired keys and the claims of requests in progress for longer than the lease are dropped first
+    pub async fn begin_idempotent_request(
+        &self,
+        account_id: &AccountId,
+        key: &str,
+        request_hash: &str
+    ) -> Result<IdempotencyState, Error> {
+        sqlx::query("DELETE FROM idempotency_keys WHERE account_id = $1 AND key = $2 \
+            AND (created_on < NOW() - make_interval(hours => $3) \
+            OR (status_code IS NULL AND created_on < NOW() - make_interval(secs => $4)))")
+            .bind(account_id.0)
+            .bind(key)
+            .bind(IDEMPOTENCY_KEY_TTL_HOURS)
+            .bind(IDEMPOTENCY_LEASE_SECONDS as f64)
+            .execute(&self.connection)
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+
+        let claimed = sqlx::query("INSERT INTO idempotency_keys (account_id, key, request_hash) \
+            VALUES ($1, $2, $3) ON CONFLICT (account_id, key) DO NOTHING")
+            .bind(account_id.0)
+            .bind(key)
+            .bind(request_hash)
+            .execute(&self.connection)
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+        if claimed.rows_affected() == 1 {
+            return Ok(IdempotencyState::New);
+        }
+
+        match sqlx::query("SELECT request_hash, status_code, response_headers, response_body \
+            FROM idempotency_keys WHERE account_id = $1 AND key = $2")
+            .bind(account_id.0)
+            .bind(key)
+            .map(|row: PgRow| {
+                if row.get::<String, _>("request_hash") != request_hash {
+                    return IdempotencyState::Mismatch;
+                }
+                match row.get::<Option<i32>, _>("status_code") {
+                    None => IdempotencyState::InProgress,
+                    Some(status_code) => {
+                        let headers: Option<serde_json::Value> = row.get("response_headers");
+                        IdempotencyState::Completed(StoredResponse {
+                            status_code: status_code as u16,
+                            headers: headers
+                                .and_then(|headers| serde_json::from_value(headers).ok())
+                                .unwrap_or_default(),
+                            body: row.get::<Option<Vec<u8>>, _>("response_body").unwrap_or_default(),
+                        })
+                    }
+                }
+            })
+            .fetch_optional(&self.connection)
+            .await {
+            Ok(Some(state)) => Ok(state),
+            // Expired and removed by another request in between, let the client retry
+            Ok(None) => Ok(IdempotencyState::InProgress),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    ///Save the response of a request, to be replayed for every retry with the same key.
+    ///A claim lost to another request once the lease ran out is left to that request
+    pub async fn complete_idempotent_request(
+        &self,
+        account_id: &AccountId,
+        key: &str,
+        request_hash: &str,
+        response: &StoredResponse
+    ) -> Result<(), Error> {
+        match sqlx::query("UPDATE idempotency_keys SET status_code = $1, response_headers = $2, \
+            response_body = $3 WHERE account_id = $4 AND key = $5 AND request_hash = $6 AND status_code IS NULL")
+            .bind(response.status_code as i32)
+            .bind(serde_json::json!(response.headers))
+            .bind(&response.body)
+            .bind(account_id.0)
+            .bind(key)
+            .bind(request_hash)
+            .execute(&self.connection)
+            .await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    ///Release a key whose request failed, so that the client can retry it
+    pub async fn release_idempotency_key(
+        &self,
+        account_id: &AccountId,
+        key: &str
+    ) -> Result<(), Error> {
+        match sqlx::query("DELETE FROM idempotency_keys WHERE account_id = $1 AND key = $2 \
+            AND status_code IS NULL")
+            .bind(account_id.0)
+            .bind(key)
+            .execute(&self.connection)
+            .await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use std::time::Duration;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgListener;
+use sqlx::PgConnection;
+
+use handle_errors::Error;
+
+use crate::store::Store;
+use crate::types::accounts::AccountId;
+use crate::types::events::{ProductEvent, ProductEventKind, StoreChange};
+
+/// Postgres channel the instances send their changes on
+pub const CHANGES_CHANNEL: &str = "store_changes";
+/// Wait between two attempts to reach the database once the listener lost it
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Payload of a notification, `origin` tells which instance committed the change
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Notification {
+    origin: String,
+    #[serde(flatten)]
+    change: StoreChange,
+}
+
+/// Identity of this instance on the changes channel, shared by the clones of the store
+#[derive(Debug, Clone)]
+pub struct ChangeFeed {
+    origin: String,
+}
+
+impl ChangeFeed {
+    pub fn new() -> Self {
+        ChangeFeed { origin: hex::encode(rand::thread_rng().gen::<[u8; 8]>()) }
+    }
+
+    /// Random id of this instance, its own notifications aren't applied twice
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+}
+
+impl Default for ChangeFeed {
+    fn default() -> Self {
+        ChangeFeed::new()
+    }
+}
+
+/// Send a change to every instance, meant to run in the transaction of the change so that it
+/// is only delivered once committed
+pub(crate) async fn notify(
+    connection: &mut PgConnection,
+    feed: &ChangeFeed,
+    change: StoreChange
+) -> Result<(), Error> {
+    let payload = serde_json::to_string(&Notification { origin: feed.origin.clone(), change })
+        .expect("Changes serialize to JSON");
+    match sqlx::query("SELECT pg_notify($1, $2)")
+        .bind(CHANGES_CHANNEL)
+        .bind(payload)
+        .execute(connection)
+        .await {
+        Ok(_) => Ok(()),
+        Err(error) => {
+            tracing::event!(tracing::Level::ERROR, "{:?}", error);
+            Err(Error::DatabaseQueryError(error))
+        }
+    }
+}
+
+impl Store {
+    /// Listen to the changes committed by the other instances and apply them to the cached products
+    /// and the product streams, this instance did so when committing its own. Notifications sent
+    /// while the connection is down are lost, so the whole cache is dropped and the streams are told
+    /// to reload once it is back. Meant to be spawned, it never returns
+    pub async fn listen_for_changes(self) {
+        let mut listener = loop {
+            match self.listener().await {
+                Ok(listener) => {
+                    tracing::event!(tracing::Level::INFO, "Listening to store changes as instance {}", self.changes.origin());
+                    break listener;
+                }
+                Err(error) => {
+                    tracing::event!(tracing::Level::ERROR, "Cannot listen to store changes: {:?}", error);
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            }
+        };
+
+        loop {
+            match listener.try_recv().await {
+                Ok(Some(notification)) => self.received(notification.payload()).await,
+                //The listener reconnects on the next call
+                Ok(None) => {
+                    tracing::event!(tracing::Level::WARN, "Lost the store changes connection, reconnecting");
+                    self.resync();
+                }
+                Err(error) => {
+                    tracing::event!(tracing::Level::ERROR, "Cannot listen to store changes: {:?}", error);
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                    self.resync();
+                }
+            }
+        }
+    }
+
+    /// Forget what may have changed unnoticed
+    fn resync(&self) {
+        self.product_cache.clear();
+        self.product_events.resync();
+    }
+
+    async fn listener(&self) -> Result<PgListener, sqlx::Error> {
+        let mut listener = PgListener::connect_with(&self.connection).await?;
+        listener.listen(CHANGES_CHANNEL).await?;
+        Ok(listener)
+    }
+
+    async fn received(&self, payload: &str) {
+        let notification: Notification = match serde_json::from_str(payload) {
+            Ok(notification) => notification,
+            Err(error) => {
+                tracing::event!(tracing::Level::WARN, "Unknown store change {}: {:?}", payload, error);
+                return;
+            }
+        };
+        if notification.origin != self.changes.origin {
+            self.apply(&notification.change).await;
+        }
+    }
+
+    /// Bring the cache and the product streams up to date with a change of another instance
+    async fn apply(&self, change: &StoreChange) {
+        match change {
+            StoreChange::Product { kind, product_id, seller_id } => {
+                self.product_cache.invalidate_product(*product_id);
+                if self.product_events.subscribers() == 0 {
+                    return;
+                }
+                let event = match kind {
+                    ProductEventKind::Deleted => ProductEvent::deleted(*product_id, seller_id.map(AccountId)),
+                    //Notifications only carry ids, the product is read back
+                    _ => match self.clone().get_product_by_id(*product_id).await {
+                        Ok(product) if *kind == ProductEventKind::Created => ProductEvent::created(&product),
+                        Ok(product) => ProductEvent::updated(&product),
+                        //Deleted since, its own notification follows
+                        Err(_) => return,
+                    },
+                };
+                self.product_events.publish(event);
+            }
+            StoreChange::Reviews { product_id } => self.product_cache.invalidate_product(*product_id),
+            //Imports and category changes touch products in bulk, the streams reload rather than replay them
+            StoreChange::Categories | StoreChange::Catalog { .. } => self.resync(),
+            StoreChange::Account { account_id, action } => {
+                tracing::event!(tracing::Level::INFO, account_id, action, "Account changed by another instance");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod notify_test {
+    use super::{ChangeFeed, Duration, Notification, ProductEventKind, StoreChange};
+    use crate::store::{Delivery, ProductCache, ProductEvents, Store};
+    use crate::types::accounts::AccountId;
+    use crate::types::events::EventFilter;
+    use futures_util::StreamExt;
+
+    #[test]
+    fn notification_payload() {
+        let feed = ChangeFeed::new();
+        assert_eq!(feed.origin().len(), 16);
+        assert_ne!(feed.origin(), ChangeFeed::new().origin());
+
+        let notification = Notification {
+            origin: feed.origin().to_string(),
+            change: StoreChange::product(ProductEventKind::Deleted, 7, Some(&AccountId(2))),
+        };
+        let json = serde_json::to_string(&notification).unwrap();
+        assert!(json.starts_with(&format!(r#"{{"origin":"{}","change":"product""#, feed.origin())));
+        assert_eq!(serde_json::from_str::<Notification>(&json).unwrap(), notification);
+    }
+
+    #[tokio::test]
+    async fn remote_import_resyncs_streams() {
+        //Bulk changes are applied without a database round trip
+        let store = Store {
+            connection: sqlx::postgres::PgPoolOptions::new()
+                .connect_lazy("postgres://localhost/unused")
+                .unwrap(),
+            product_cache: ProductCache::disabled(),
+            product_events: ProductEvents::new(4),
+            changes: ChangeFeed::new(),
+        };
+        let mut stream = Box::pin(store.product_events.subscribe(EventFilter::default()));
+
+        let notification = Notification {
+            origin: ChangeFeed::new().origin().to_string(),
+            change: StoreChange::Catalog { seller_id: 2 },
+        };
+        store.received(&serde_json::to_string(&notification).unwrap()).await;
+        let delivery = tokio::time::timeout(Duration::from_secs(1), stream.next()).await;
+        assert!(matches!(delivery, Ok(Some(Delivery::Resync))));
+    }
+}
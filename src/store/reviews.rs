@@ -0,0 +1,225 @@
+use sqlx::{postgres::PgRow, PgConnection, Row};
+
+use handle_errors::Error;
+
+use crate::store::{commit, notify, record_event, Store};
+use crate::types::accounts::AccountId;
+use crate::types::audit::{AuditAction, NewAuditEvent};
+use crate::types::events::StoreChange;
+use crate::types::products::ProductId;
+use crate::types::reviews::{NewReview, Review, ReviewId};
+
+const REVIEW_COLUMNS: &str = "id, product_id, author_id, rating, body, created_on, updated_on";
+
+fn review_from_row(row: PgRow) -> Review {
+    Review {
+        id: ReviewId(row.get("id")),
+        product_id: ProductId(row.get("product_id")),
+        author_id: AccountId(row.get("author_id")),
+        rating: row.get("rating"),
+        body: row.get("body"),
+        created_on: row.get("created_on"),
+        updated_on: row.get("updated_on"),
+    }
+}
+
+/// Bump the reviews version and modification date of a reviewed product, its rating is part
+/// of it and the validators of the cached copies must change along. The version is left alone,
+/// the seller editing the product meanwhile mustn't be told it changed
+async fn touch_product(connection: &mut PgConnection, product_id: i32) -> Result<(), Error> {
+    match sqlx::query("UPDATE products SET updated_on = NOW(), reviews_version = reviews_version + 1 WHERE id = $1")
+        .bind(product_id)
+        .execute(connection)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            Err(Error::DatabaseQueryError(e))
+        }
+    }
+}
+
+impl Store {
+    ///Get a page of the reviews of a product, newest first
+    pub async fn get_reviews(
+        self,
+        product_id: i32,
+        limit: Option<i32>,
+        offset: i32
+    ) -> Result<Vec<Review>, Error> {
+        match sqlx::query(&format!(
+            "SELECT {} FROM reviews WHERE product_id = $1 \
+            ORDER BY created_on DESC, id DESC LIMIT $2 OFFSET $3",
+            REVIEW_COLUMNS
+        ))
+            .bind(product_id)
+            .bind(limit)
+            .bind(offset)
+            .map(review_from_row)
+            .fetch_all(&self.connection)
+            .await
+        {
+            Ok(reviews) => Ok(reviews),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    ///Review a product. An account reviews a product once and never one of its own products.
+    ///Once orders exist, the author will also need to have bought the product
+    pub async fn add_review(
+        self,
+        product_id: i32,
+        review: NewReview,
+        author_id: AccountId
+    ) -> Result<Review, Error> {
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+
+        let product = sqlx::query("SELECT seller_id FROM products WHERE id = $1 AND deleted_at IS NULL")
+            .bind(product_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(Error::DatabaseQueryError)?
+            .ok_or(Error::NotFound)?;
+        if product.get::<Option<i32>, _>("seller_id") == Some(author_id.0) {
+            return Err(Error::OwnProduct);
+        }
+
+        let created = match sqlx::query(&format!(
+            "INSERT INTO reviews (product_id, author_id, rating, body) VALUES ($1, $2, $3, $4) \
+            ON CONFLICT (product_id, author_id) DO NOTHING RETURNING {}",
+            REVIEW_COLUMNS
+        ))
+            .bind(product_id)
+            .bind(author_id.0)
+            .bind(review.rating)
+            .bind(review.body)
+            .map(review_from_row)
+            .fetch_optional(&mut *tx)
+            .await
+        {
+            Ok(Some(created)) => created,
+            Ok(None) => return Err(Error::AlreadyReviewed),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(author_id, AuditAction::ReviewCreate, created.id.0)
+                .after(Some(&created)),
+        ).await?;
+        touch_product(&mut tx, product_id).await?;
+        notify(&mut tx, &self.changes, StoreChange::Reviews { product_id }).await?;
+        commit(tx).await?;
+        //The rating of the product changed
+        self.product_cache.invalidate_product(product_id);
+        Ok(created)
+    }
+
+    ///Change the rating and text of a review, only its author can
+    pub async fn update_review(
+        self,
+        id: i32,
+        review: NewReview,
+        author_id: AccountId
+    ) -> Result<Review, Error> {
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+
+        let before = match sqlx::query(&format!(
+            "SELECT {} FROM reviews WHERE id = $1 FOR UPDATE", REVIEW_COLUMNS
+        ))
+            .bind(id)
+            .map(review_from_row)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(Error::DatabaseQueryError)?
+        {
+            Some(before) if before.author_id == author_id => before,
+            Some(_) => return Err(Error::Unauthorized),
+            None => return Err(Error::NotFound),
+        };
+
+        let after = match sqlx::query(&format!(
+            "UPDATE reviews SET rating = $1, body = $2, updated_on = NOW() WHERE id = $3 RETURNING {}",
+            REVIEW_COLUMNS
+        ))
+            .bind(review.rating)
+            .bind(review.body)
+            .bind(id)
+            .map(review_from_row)
+            .fetch_one(&mut *tx)
+            .await
+        {
+            Ok(after) => after,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(author_id, AuditAction::ReviewUpdate, id)
+                .before(Some(&before))
+                .after(Some(&after)),
+        ).await?;
+        touch_product(&mut tx, after.product_id.0).await?;
+        notify(&mut tx, &self.changes, StoreChange::Reviews { product_id: after.product_id.0 }).await?;
+        commit(tx).await?;
+        self.product_cache.invalidate_product(after.product_id.0);
+        Ok(after)
+    }
+
+    ///Delete a review, allowed to its author and to admins
+    pub async fn delete_review(
+        self,
+        id: i32,
+        account_id: AccountId
+    ) -> Result<bool, Error> {
+        let is_admin = self.is_admin(&account_id).await?;
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+
+        let before = match sqlx::query(&format!(
+            "SELECT {} FROM reviews WHERE id = $1 FOR UPDATE", REVIEW_COLUMNS
+        ))
+            .bind(id)
+            .map(review_from_row)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(Error::DatabaseQueryError)?
+        {
+            Some(before) if is_admin || before.author_id == account_id => before,
+            Some(_) => return Err(Error::Unauthorized),
+            None => return Err(Error::NotFound),
+        };
+
+        match sqlx::query("DELETE FROM reviews WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+        {
+            Ok(_) => (),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        }
+
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(account_id, AuditAction::ReviewDelete, id)
+                .before(Some(&before)),
+        ).await?;
+        touch_product(&mut tx, before.product_id.0).await?;
+        notify(&mut tx, &self.changes, StoreChange::Reviews { product_id: before.product_id.0 }).await?;
+        commit(tx).await?;
+        self.product_cache.invalidate_product(before.product_id.0);
+        Ok(true)
+    }
+}
@@ -0,0 +1,54 @@
+use sqlx::{postgres::PgRow, Row};
+
+use handle_errors::Error;
+
+use crate::store::{product_from_row, Store, PRODUCT_COLUMNS};
+use crate::types::search::{highlight, SearchResult, MATCH_END, MATCH_START};
+
+/// Options of `ts_headline`, at most two fragments are kept and matches are wrapped in sentinels
+/// which `highlight` turns into `<mark>` once the text is escaped
+fn headline_options() -> String {
+    format!("StartSel={}, StopSel={}, MaxFragments=2, MaxWords=20, MinWords=5", MATCH_START, MATCH_END)
+}
+
+fn search_result_from_row(row: PgRow) -> SearchResult {
+    SearchResult {
+        rank: row.get("rank"),
+        snippet: highlight(row.get("snippet")),
+        product: product_from_row(row),
+    }
+}
+
+impl Store {
+    ///Full-text search over the name and description of live products, best matches first
+    pub async fn search_products(
+        self,
+        query: String,
+        limit: Option<i32>,
+        offset: i32
+    ) -> Result<Vec<SearchResult>, Error> {
+        match sqlx::query(&format!(
+            "SELECT {}, ts_rank(p.search_vector, q) AS rank, \
+            ts_headline('english', translate(p.name || ' ' || p.description, $5, ''), q, $2) AS snippet \
+            FROM products p, websearch_to_tsquery('english', $1) q \
+            WHERE p.deleted_at IS NULL AND p.search_vector @@ q \
+            ORDER BY rank DESC, p.id LIMIT $3 OFFSET $4",
+            PRODUCT_COLUMNS
+        ))
+            .bind(query)
+            .bind(headline_options())
+            .bind(limit)
+            .bind(offset)
+            .bind(format!("{}{}", MATCH_START, MATCH_END))
+            .map(search_result_from_row)
+            .fetch_all(&self.connection)
+            .await
+        {
+            Ok(results) => Ok(results),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use sqlx::{postgres::PgRow, PgConnection, Row};
+
+use handle_errors::Error;
+
+use crate::store::{commit, notify, record_event, Store};
+use crate::types::accounts::{AccountId, SELLER_ROLE};
+use crate::types::audit::{AuditAction, NewAuditEvent};
+use crate::types::events::StoreChange;
+use crate::types::sellers::{SellerProfile, UpdateSellerProfile};
+
+/// Public columns of an account, password and role are never part of a profile.
+/// Unless `$2` is false, only accounts with the seller role or which listed a product are found
+const SELLER_PROFILE_QUERY: &str = "SELECT a.id, COALESCE(a.display_name, 'Seller ' || a.id) AS display_name, \
+    a.bio, a.created_on, \
+    (SELECT COUNT(*) FROM products p WHERE p.seller_id = a.id AND p.deleted_at IS NULL) AS product_count \
+    FROM accounts a WHERE a.id = $1 \
+    AND (NOT $2 OR a.role = $3 OR EXISTS (SELECT 1 FROM products p WHERE p.seller_id = a.id))";
+
+fn seller_profile_from_row(row: PgRow) -> SellerProfile {
+    SellerProfile {
+        id: AccountId(row.get("id")),
+        display_name: row.get("display_name"),
+        bio: row.get("bio"),
+        joined_on: row.get("created_on"),
+        product_count: row.get("product_count"),
+    }
+}
+
+async fn fetch_seller_profile(
+    connection: &mut PgConnection,
+    id: i32,
+    sellers_only: bool
+) -> Result<SellerProfile, Error> {
+    match sqlx::query(SELLER_PROFILE_QUERY)
+        .bind(id)
+        .bind(sellers_only)
+        .bind(SELLER_ROLE)
+        .map(seller_profile_from_row)
+        .fetch_optional(connection)
+        .await
+    {
+        Ok(Some(profile)) => Ok(profile),
+        Ok(None) => Err(Error::NotFound),
+        Err(e) => {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            Err(Error::DatabaseQueryError(e))
+        }
+    }
+}
+
+impl Store {
+    ///Get the public profile of a seller, an account with the seller role or which listed a product
+    pub async fn get_seller_profile(self, id: i32) -> Result<SellerProfile, Error> {
+        let mut connection = self.connection.acquire().await.map_err(Error::DatabaseQueryError)?;
+        fetch_seller_profile(&mut connection, id, true).await
+    }
+
+    ///Change the display name or bio of an account, fields left out are kept
+    pub async fn update_seller_profile(
+        self,
+        account_id: AccountId,
+        profile: UpdateSellerProfile
+    ) -> Result<SellerProfile, Error> {
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+        let before = fetch_seller_profile(&mut tx, account_id.0, false).await?;
+
+        match sqlx::query("UPDATE accounts SET display_name = COALESCE($1, display_name), \
+            bio = COALESCE($2, bio) WHERE id = $3")
+            .bind(profile.display_name.map(|name| name.trim().to_string()))
+            .bind(profile.bio)
+            .bind(account_id.0)
+            .execute(&mut *tx)
+            .await
+        {
+            Ok(_) => (),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        }
+
+        let after = fetch_seller_profile(&mut tx, account_id.0, false).await?;
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(account_id.clone(), AuditAction::AccountUpdateProfile, account_id.0)
+                .before(Some(&before))
+                .after(Some(&after)),
+        ).await?;
+        notify(&mut tx, &self.changes, StoreChange::account(&account_id, AuditAction::AccountUpdateProfile)).await?;
+        commit(tx).await?;
+        Ok(after)
+    }
+}
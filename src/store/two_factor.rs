@@ -0,0 +1,214 @@
+use chrono::prelude::*;
+use sqlx::{PgConnection, Row};
+
+use handle_errors::Error;
+
+use crate::store::account_tokens::consume_token;
+use crate::store::{commit, notify, record_event, Store};
+use crate::types::account_tokens::TokenPurpose;
+use crate::types::accounts::AccountId;
+use crate::types::audit::{AuditAction, NewAuditEvent};
+use crate::types::events::StoreChange;
+use crate::types::two_factor::{
+    generate_backup_codes, generate_secret, hash_backup_code, is_totp_code, otpauth_uri,
+    verify_totp, BackupCodes, TotpEnrollment,
+};
+
+/// Two-factor state of an account, locked for the rest of the transaction
+struct TwoFactorState {
+    secret: Option<String>,
+    enabled: bool,
+    last_step: Option<i64>,
+}
+
+async fn lock_two_factor_state(
+    connection: &mut PgConnection,
+    account_id: &AccountId
+) -> Result<TwoFactorState, Error> {
+    match sqlx::query("SELECT totp_secret, totp_enabled_on IS NOT NULL AS enabled, totp_last_step \
+        FROM accounts WHERE id = $1 FOR UPDATE")
+        .bind(account_id.0)
+        .fetch_optional(connection)
+        .await
+    {
+        Ok(Some(row)) => Ok(TwoFactorState {
+            secret: row.get("totp_secret"),
+            enabled: row.get("enabled"),
+            last_step: row.get("totp_last_step"),
+        }),
+        Ok(None) => Err(Error::NotFound),
+        Err(e) => {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            Err(Error::DatabaseQueryError(e))
+        }
+    }
+}
+
+/// Accept a code of the authenticator app newer than the last accepted one,
+/// and remember its time step so that it can't be replayed
+async fn accept_totp_code(
+    connection: &mut PgConnection,
+    account_id: &AccountId,
+    state: &TwoFactorState,
+    code: &str
+) -> Result<(), Error> {
+    let secret = state.secret.as_deref().ok_or(Error::TwoFactorNotEnabled)?;
+    let step = verify_totp(secret, code, Utc::now().timestamp())
+        .filter(|step| state.last_step.map_or(true, |last| *step > last))
+        .ok_or(Error::InvalidSecondFactor)?;
+
+    sqlx::query("UPDATE accounts SET totp_last_step = $1 WHERE id = $2")
+        .bind(step)
+        .bind(account_id.0)
+        .execute(connection)
+        .await
+        .map(|_| ())
+        .map_err(Error::DatabaseQueryError)
+}
+
+/// Check the second factor of an account with two-factor authentication enabled,
+/// either a code of the authenticator app or an unused backup code, which is then used up
+async fn verify_second_factor(
+    connection: &mut PgConnection,
+    account_id: &AccountId,
+    code: &str
+) -> Result<(), Error> {
+    let state = lock_two_factor_state(connection, account_id).await?;
+    if !state.enabled {
+        return Err(Error::TwoFactorNotEnabled);
+    }
+    if is_totp_code(code) {
+        return accept_totp_code(connection, account_id, &state, code).await;
+    }
+
+    match sqlx::query("UPDATE totp_backup_codes SET used_on = NOW() \
+        WHERE id = (SELECT id FROM totp_backup_codes \
+        WHERE account_id = $1 AND code_hash = $2 AND used_on IS NULL LIMIT 1) RETURNING id")
+        .bind(account_id.0)
+        .bind(hash_backup_code(code))
+        .fetch_optional(connection)
+        .await
+    {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(Error::InvalidSecondFactor),
+        Err(e) => {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            Err(Error::DatabaseQueryError(e))
+        }
+    }
+}
+
+impl Store {
+    ///Start the enrollment of an authenticator app, a new secret replaces an unconfirmed one
+    pub async fn enroll_two_factor(&self, account_id: &AccountId) -> Result<TotpEnrollment, Error> {
+        let secret = generate_secret();
+        match sqlx::query("UPDATE accounts SET totp_secret = $1, totp_last_step = NULL \
+            WHERE id = $2 AND totp_enabled_on IS NULL RETURNING username")
+            .bind(&secret)
+            .bind(account_id.0)
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(Some(row)) => Ok(TotpEnrollment {
+                otpauth_uri: otpauth_uri(row.get("username"), &secret),
+                secret,
+            }),
+            Ok(None) => Err(Error::TwoFactorAlreadyEnabled),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    ///Enable two-factor authentication with a first code of the enrolled app.
+    ///The backup codes are only stored hashed, they are returned this once
+    pub async fn confirm_two_factor(self, account_id: &AccountId, code: String) -> Result<BackupCodes, Error> {
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+        let state = lock_two_factor_state(&mut tx, account_id).await?;
+        if state.enabled {
+            return Err(Error::TwoFactorAlreadyEnabled);
+        }
+        accept_totp_code(&mut tx, account_id, &state, &code).await?;
+
+        sqlx::query("UPDATE accounts SET totp_enabled_on = NOW() WHERE id = $1")
+            .bind(account_id.0)
+            .execute(&mut *tx)
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+
+        let backup_codes = generate_backup_codes();
+        let hashes: Vec<String> = backup_codes.iter().map(|code| hash_backup_code(code)).collect();
+        sqlx::query("DELETE FROM totp_backup_codes WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&mut *tx)
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+        match sqlx::query("INSERT INTO totp_backup_codes (account_id, code_hash) \
+            SELECT $1, UNNEST($2::text[])")
+            .bind(account_id.0)
+            .bind(&hashes)
+            .execute(&mut *tx)
+            .await
+        {
+            Ok(_) => (),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        }
+
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(account_id.clone(), AuditAction::AccountEnableTwoFactor, account_id.0),
+        ).await?;
+        notify(&mut tx, &self.changes, StoreChange::account(account_id, AuditAction::AccountEnableTwoFactor)).await?;
+        commit(tx).await?;
+        Ok(BackupCodes { backup_codes })
+    }
+
+    ///Trade a login challenge and a second factor for the account to open a session for.
+    ///The challenge is used up even when the code is wrong, a new login is needed to try again
+    pub async fn complete_two_factor_login(
+        self,
+        challenge_token: String,
+        code: String
+    ) -> Result<AccountId, Error> {
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+        let account_id = consume_token(&mut tx, &challenge_token, TokenPurpose::TwoFactorChallenge).await?;
+        let verified = verify_second_factor(&mut tx, &account_id, &code).await;
+        commit(tx).await?;
+        verified.map(|_| account_id)
+    }
+
+    ///Turn two-factor authentication off, the caller checks the password beforehand
+    pub async fn disable_two_factor(self, account_id: &AccountId, code: String) -> Result<bool, Error> {
+        let mut tx = self.connection.begin().await.map_err(Error::DatabaseQueryError)?;
+        verify_second_factor(&mut tx, account_id, &code).await?;
+
+        sqlx::query("DELETE FROM totp_backup_codes WHERE account_id = $1")
+            .bind(account_id.0)
+            .execute(&mut *tx)
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+        match sqlx::query("UPDATE accounts SET totp_secret = NULL, totp_enabled_on = NULL, \
+            totp_last_step = NULL WHERE id = $1")
+            .bind(account_id.0)
+            .execute(&mut *tx)
+            .await
+        {
+            Ok(_) => (),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        }
+
+        record_event(
+            &mut tx,
+            NewAuditEvent::new(account_id.clone(), AuditAction::AccountDisableTwoFactor, account_id.0),
+        ).await?;
+        notify(&mut tx, &self.changes, StoreChange::account(account_id, AuditAction::AccountDisableTwoFactor)).await?;
+        commit(tx).await.map(|_| true)
+    }
+}
@@ -0,0 +1,113 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use sha2::{Digest, Sha256};
+
+use crate::types::accounts::{check_email, check_password};
+use crate::types::validation::{Validate, Validator};
+
+/// What a token sent by mail allows, a token is only accepted for its own purpose
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenPurpose {
+    VerifyEmail,
+    ResetPassword,
+    /// Issued by `login` to accounts with two-factor authentication, traded for a session
+    /// along a second factor
+    TwoFactorChallenge,
+}
+
+impl TokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenPurpose::VerifyEmail => "verify_email",
+            TokenPurpose::ResetPassword => "reset_password",
+            TokenPurpose::TwoFactorChallenge => "two_factor_challenge",
+        }
+    }
+
+    /// Minutes a token stays valid after it was issued
+    pub fn ttl_minutes(&self) -> i32 {
+        match self {
+            TokenPurpose::VerifyEmail => 48 * 60,
+            TokenPurpose::ResetPassword => 60,
+            TokenPurpose::TwoFactorChallenge => 5,
+        }
+    }
+}
+
+/// Random token sent by mail or handed out by `login`, only its hash is stored
+pub fn generate_token() -> String {
+    hex::encode(rand::thread_rng().gen::<[u8; 32]>())
+}
+
+/// Hash under which a token is stored, a leaked table can't be used to verify or reset accounts
+/// # Example usage
+/// ```rust
+/// use restful_api::types::account_tokens::{generate_token, hash_token};
+///
+/// let token = generate_token();
+/// assert_eq!(hash_token(&token).len(), 64);
+/// assert_ne!(hash_token(&token), token);
+/// ```
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.trim().as_bytes()))
+}
+
+/// Body of `POST /email/verify`
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct VerifyEmail {
+    pub token: String,
+}
+
+impl Validate for VerifyEmail {
+    fn check(&self, validator: &mut Validator) {
+        validator.length("token", &self.token, 1, 128);
+    }
+}
+
+/// Body of `POST /password/forgot`
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct ForgotPassword {
+    pub email: String,
+}
+
+impl Validate for ForgotPassword {
+    fn check(&self, validator: &mut Validator) {
+        check_email(validator, "email", &self.email);
+    }
+}
+
+/// Body of `POST /password/reset`, holds the new password in clear so it is never serialized
+#[derive(Deserialize, Clone, ToSchema)]
+pub struct ResetPassword {
+    pub token: String,
+    pub password: String,
+}
+
+impl Validate for ResetPassword {
+    fn check(&self, validator: &mut Validator) {
+        validator.length("token", &self.token, 1, 128);
+        check_password(validator, "password", &self.password);
+    }
+}
+
+#[cfg(test)]
+mod account_tokens_test {
+    use super::{generate_token, hash_token, ResetPassword};
+    use crate::types::validation::Validate;
+
+    #[test]
+    fn tokens_are_unique() {
+        let token = generate_token();
+        assert_eq!(token.len(), 64);
+        assert_ne!(token, generate_token());
+        assert_eq!(hash_token(&token), hash_token(&format!(" {}\n", token)));
+    }
+
+    #[test]
+    fn reset_password_rules() {
+        let reset = ResetPassword { token: "abc".to_string(), password: "short".to_string() };
+        let result = reset.validate().unwrap_err();
+        assert_eq!(format!("{}", result), "Invalid fields: password");
+    }
+}
@@ -1,19 +1,195 @@
 use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::types::api_keys::ApiKeyId;
+use crate::types::validation::{Validate, Validator};
+
+/// Role of the accounts allowed to manage shared resources such as categories
+pub const ADMIN_ROLE: &str = "admin";
+/// Role of the accounts registered to sell, others become sellers by listing a product
+pub const SELLER_ROLE: &str = "seller";
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Session {
     pub exp: DateTime<Utc>,
     pub account_id: AccountId,
+    /// Set when the request is authenticated with an API key instead of a login
+    #[serde(default)]
+    pub api_key_id: Option<ApiKeyId>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Account {
-    pub id: Option<AccountId>,
+/// Reply of `POST /login` and `POST /login/2fa`, the token is sent back as
+/// `Authorization: Bearer <access_token>`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct AccessToken {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Body of `POST /registration`, holds the password in clear so it is never serialized
+#[derive(Deserialize, Clone, ToSchema)]
+pub struct NewAccount {
+    pub username: String,
+    pub password: String,
+    pub role: String,
+    /// Has to be verified before the account can sell
+    pub email: String,
+}
+
+/// Body of `POST /login`
+#[derive(Deserialize, Clone, ToSchema)]
+pub struct Credentials {
     pub username: String,
     pub password: String,
+}
+
+impl Validate for Credentials {
+    fn check(&self, validator: &mut Validator) {
+        validator.length("username", &self.username, 1, 255).check(
+            "password",
+            (1..=128).contains(&self.password.chars().count()),
+            "length",
+            "must be between 1 and 128 characters long",
+        );
+    }
+}
+
+/// An account as handed out by the store, safe to be sent to clients
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct Account {
+    pub id: AccountId,
+    pub username: String,
     pub role: String,
+    pub email: Option<String>,
+    pub email_verified_on: Option<DateTime<Utc>>,
+    pub created_on: DateTime<Utc>,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+/// A row of the `accounts` table. It carries the password hash, which is why it can't be
+/// serialized: it is turned into an `Account` as soon as the password is checked
+#[derive(Clone)]
+pub struct AccountRecord {
+    pub id: AccountId,
+    pub username: String,
+    pub password_hash: String,
+    pub role: String,
+    pub email: Option<String>,
+    pub email_verified_on: Option<DateTime<Utc>>,
+    /// Set once two-factor authentication is confirmed, `login` then asks for a code
+    pub totp_enabled_on: Option<DateTime<Utc>>,
+    pub created_on: DateTime<Utc>,
+}
+
+impl From<AccountRecord> for Account {
+    fn from(record: AccountRecord) -> Self {
+        Account {
+            id: record.id,
+            username: record.username,
+            role: record.role,
+            email: record.email,
+            email_verified_on: record.email_verified_on,
+            created_on: record.created_on,
+        }
+    }
+}
+
+/// Registration rules, the admin role can't be picked by the registering user
+impl Validate for NewAccount {
+    fn check(&self, validator: &mut Validator) {
+        validator.length("username", &self.username, 1, 255);
+        check_password(validator, "password", &self.password);
+        check_email(validator, "email", &self.email);
+        validator
+            .length("role", &self.role, 1, 255)
+            .check("role", self.role != ADMIN_ROLE, "forbidden", "cannot be chosen at registration");
+    }
+}
+
+/// Password rules, shared by registration and password reset
+pub fn check_password(validator: &mut Validator, field: &str, password: &str) {
+    validator.check(
+        field,
+        (8..=128).contains(&password.chars().count()),
+        "length",
+        "must be between 8 and 128 characters long",
+    );
+}
+
+/// Loose shape check of an email address, the verification mail tells whether it exists
+pub fn check_email(validator: &mut Validator, field: &str, email: &str) {
+    let email = email.trim();
+    let valid = match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !domain.contains('@')
+        }
+        None => false,
+    };
+    validator
+        .length(field, email, 3, 255)
+        .check(
+            field,
+            valid && !email.chars().any(char::is_whitespace),
+            "email",
+            "must be an email address",
+        );
+}
+
+/// Emails are stored lowercase and trimmed, so that an address is only registered once
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash, ToSchema)]
 pub struct AccountId(pub i32);
+
+#[cfg(test)]
+mod accounts_test {
+    use super::{check_email, Account, AccountId, AccountRecord, Credentials, Utc};
+    use crate::types::validation::{Validate, Validator};
+
+    #[test]
+    fn record_conversion_strips_password_hash() {
+        let record = AccountRecord {
+            id: AccountId(1),
+            username: "seller".to_string(),
+            password_hash: "$argon2i$v=19$m=4096,t=3,p=1$c2FsdA$aGFzaA".to_string(),
+            role: "user".to_string(),
+            email: Some("seller@example.com".to_string()),
+            email_verified_on: None,
+            totp_enabled_on: None,
+            created_on: Utc::now(),
+        };
+        let json = serde_json::to_value(Account::from(record)).unwrap();
+        assert_eq!(json["username"], "seller");
+        assert!(!json.to_string().contains("argon2"));
+    }
+
+    #[test]
+    fn credentials_need_username_and_password() {
+        let credentials = Credentials { username: "seller".to_string(), password: "hunter22".to_string() };
+        assert!(credentials.validate().is_ok());
+        let credentials = Credentials { username: " ".to_string(), password: String::new() };
+        let result = format!("{}", credentials.validate().unwrap_err());
+        assert_eq!(result, "Invalid fields: username, password");
+    }
+
+    #[test]
+    fn email_shape() {
+        for email in ["user@example.com", "first.last+shop@mail.example.org"] {
+            let mut validator = Validator::default();
+            check_email(&mut validator, "email", email);
+            assert!(validator.finish().is_ok(), "{}", email);
+        }
+        for email in ["", "user", "@example.com", "user@example", "user@.com", "us er@example.com"] {
+            let mut validator = Validator::default();
+            check_email(&mut validator, "email", email);
+            assert!(validator.finish().is_err(), "{}", email);
+        }
+    }
+}
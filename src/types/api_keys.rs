@@ -0,0 +1,125 @@
+use chrono::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use warp::http::Method;
+
+use crate::types::validation::{Validate, Validator};
+
+/// Start of every API key, it tells keys apart from session tokens in logs and secret scanners
+pub const API_KEY_PREFIX: &str = "mk_";
+/// Characters of a key kept in clear, enough to recognize a key in the list
+pub const API_KEY_DISPLAY_LENGTH: usize = 11;
+
+/// What a request made with an API key may do
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiScope {
+    /// `GET` requests
+    Read,
+    /// Every other method
+    Write,
+}
+
+impl ApiScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::Read => "read",
+            ApiScope::Write => "write",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Option<Self> {
+        match scope {
+            "read" => Some(ApiScope::Read),
+            "write" => Some(ApiScope::Write),
+            _ => None,
+        }
+    }
+
+    /// Scope a request needs, depending on its method
+    pub fn for_method(method: &Method) -> Self {
+        if method == Method::GET || method == Method::HEAD {
+            ApiScope::Read
+        } else {
+            ApiScope::Write
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct ApiKeyId(pub i32);
+
+/// An API key as listed to its owner, the key itself is only shown at creation
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct ApiKey {
+    pub id: ApiKeyId,
+    pub name: String,
+    /// First characters of the key
+    pub prefix: String,
+    pub scopes: Vec<ApiScope>,
+    pub expires_on: Option<DateTime<Utc>>,
+    pub last_used_on: Option<DateTime<Utc>>,
+    pub revoked_on: Option<DateTime<Utc>>,
+    pub created_on: DateTime<Utc>,
+}
+
+/// Reply of `POST /account/api-keys`
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+/// Body of `POST /account/api-keys`, a key without `expires_on` stays valid until revoked
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct NewApiKey {
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    pub expires_on: Option<DateTime<Utc>>,
+}
+
+impl Validate for NewApiKey {
+    fn check(&self, validator: &mut Validator) {
+        validator
+            .length("name", self.name.trim(), 1, 100)
+            .check("scopes", !self.scopes.is_empty(), "length", "must hold at least one scope")
+            .check(
+                "expires_on",
+                self.expires_on.map_or(true, |expires_on| expires_on > Utc::now()),
+                "future",
+                "must be in the future",
+            );
+    }
+}
+
+/// Random key handed out once, only its hash is stored
+pub fn generate_api_key() -> String {
+    format!("{}{}", API_KEY_PREFIX, hex::encode(rand::thread_rng().gen::<[u8; 32]>()))
+}
+
+#[cfg(test)]
+mod api_keys_test {
+    use super::{generate_api_key, ApiScope, Method, NewApiKey, Utc, API_KEY_PREFIX};
+    use crate::types::validation::Validate;
+
+    #[test]
+    fn scope_of_method() {
+        assert_eq!(ApiScope::for_method(&Method::GET), ApiScope::Read);
+        assert_eq!(ApiScope::for_method(&Method::PUT), ApiScope::Write);
+        assert_eq!(ApiScope::parse(ApiScope::Write.as_str()), Some(ApiScope::Write));
+        assert!(generate_api_key().starts_with(API_KEY_PREFIX));
+    }
+
+    #[test]
+    fn new_api_key_rules() {
+        let key = NewApiKey {
+            name: " ".to_string(),
+            scopes: vec![],
+            expires_on: Some(Utc::now() - chrono::Duration::days(1)),
+        };
+        let result = key.validate().unwrap_err();
+        assert_eq!(format!("{}", result), "Invalid fields: name, scopes, expires_on");
+    }
+}
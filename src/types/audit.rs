@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use handle_errors::Error;
+
+use crate::types::accounts::AccountId;
+
+/// Mutations recorded in the audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    AccountRegister,
+    AccountUpdateProfile,
+    AccountVerifyEmail,
+    AccountResetPassword,
+    AccountEnableTwoFactor,
+    AccountDisableTwoFactor,
+    ApiKeyCreate,
+    ApiKeyRevoke,
+    ProductCreate,
+    ProductUpdate,
+    ProductDelete,
+    ProductRestore,
+    ProductPurge,
+    ProductSetCategory,
+    ProductSetTags,
+    CategoryCreate,
+    CategoryUpdate,
+    CategoryDelete,
+    ReviewCreate,
+    ReviewUpdate,
+    ReviewDelete,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::AccountRegister => "account.register",
+            AuditAction::AccountUpdateProfile => "account.update_profile",
+            AuditAction::AccountVerifyEmail => "account.verify_email",
+            AuditAction::AccountResetPassword => "account.reset_password",
+            AuditAction::AccountEnableTwoFactor => "account.enable_two_factor",
+            AuditAction::AccountDisableTwoFactor => "account.disable_two_factor",
+            AuditAction::ApiKeyCreate => "api_key.create",
+            AuditAction::ApiKeyRevoke => "api_key.revoke",
+            AuditAction::ProductCreate => "product.create",
+            AuditAction::ProductUpdate => "product.update",
+            AuditAction::ProductDelete => "product.delete",
+            AuditAction::ProductRestore => "product.restore",
+            AuditAction::ProductPurge => "product.purge",
+            AuditAction::ProductSetCategory => "product.set_category",
+            AuditAction::ProductSetTags => "product.set_tags",
+            AuditAction::CategoryCreate => "category.create",
+            AuditAction::CategoryUpdate => "category.update",
+            AuditAction::CategoryDelete => "category.delete",
+            AuditAction::ReviewCreate => "review.create",
+            AuditAction::ReviewUpdate => "review.update",
+            AuditAction::ReviewDelete => "review.delete",
+        }
+    }
+
+    /// Kind of resource the action is applied to, stored as the event `target_type`
+    pub fn target_type(&self) -> &'static str {
+        self.as_str().split('.').next().unwrap_or_default()
+    }
+}
+
+/// A recorded mutation, as returned by `GET /admin/audit`
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct AuditEvent {
+    pub id: i64,
+    pub actor_id: Option<AccountId>,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: Option<i32>,
+    #[schema(value_type = Option<Object>)]
+    pub before: Option<Value>,
+    #[schema(value_type = Option<Object>)]
+    pub after: Option<Value>,
+    pub created_on: DateTime<Utc>,
+}
+
+/// A mutation about to be recorded, along the state of its target before and after
+#[derive(Debug, Clone)]
+pub struct NewAuditEvent {
+    pub actor_id: AccountId,
+    pub action: AuditAction,
+    pub target_id: i32,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+impl NewAuditEvent {
+    pub fn new(actor_id: AccountId, action: AuditAction, target_id: i32) -> Self {
+        NewAuditEvent {
+            actor_id,
+            action,
+            target_id,
+            before: None,
+            after: None,
+        }
+    }
+
+    pub fn before<T: Serialize>(mut self, before: Option<&T>) -> Self {
+        self.before = before.and_then(|value| serde_json::to_value(value).ok());
+        self
+    }
+
+    pub fn after<T: Serialize>(mut self, after: Option<&T>) -> Self {
+        self.after = after.and_then(|value| serde_json::to_value(value).ok());
+        self
+    }
+}
+
+/// Filters which can be attached to `GET /admin/audit`
+#[derive(Default, Debug, PartialEq)]
+pub struct AuditFilter {
+    pub actor_id: Option<i32>,
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<i32>,
+}
+
+/// Extract the audit log filters from the query parameters
+/// # Example query
+/// `/admin/audit?actor=3&target_type=product&target_id=12&limit=20&offset=0`
+pub fn extract_audit_filter(
+    params: &HashMap<String, String>,
+) -> Result<AuditFilter, Error> {
+    let parse = |key: &str| {
+        params
+            .get(key)
+            .map(|value| value.parse::<i32>())
+            .transpose()
+            .map_err(Error::ParseError)
+    };
+
+    Ok(AuditFilter {
+        actor_id: parse("actor")?,
+        action: params.get("action").cloned(),
+        target_type: params.get("target_type").cloned(),
+        target_id: parse("target_id")?,
+    })
+}
+
+#[cfg(test)]
+mod audit_test {
+    use super::{extract_audit_filter, AuditAction, AuditFilter, HashMap};
+
+    #[test]
+    fn action_target_type() {
+        assert_eq!(AuditAction::ProductSetTags.target_type(), "product");
+        assert_eq!(AuditAction::AccountRegister.target_type(), "account");
+    }
+
+    #[test]
+    fn audit_filter() {
+        let mut params = HashMap::new();
+        params.insert(String::from("actor"), String::from("3"));
+        params.insert(String::from("action"), String::from("product.update"));
+        let expected = AuditFilter {
+            actor_id: Some(3),
+            action: Some(String::from("product.update")),
+            ..AuditFilter::default()
+        };
+        assert_eq!(extract_audit_filter(&params).unwrap(), expected);
+    }
+}
@@ -0,0 +1,77 @@
+use handle_errors::Error;
+
+/// Scheme of the session tokens handed out by `login`, `Authorization: Bearer <token>`
+pub const BEARER_SCHEME: &str = "Bearer";
+/// Scheme of the API keys, `Authorization: ApiKey <key>`
+pub const API_KEY_SCHEME: &str = "ApiKey";
+
+/// Credentials carried by the `Authorization` header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Authorization {
+    Bearer(String),
+    ApiKey(String),
+}
+
+/// Parse an `Authorization` header, schemes are case insensitive as per RFC 7235.
+/// A header without scheme is taken as a bare session token, as sent by older clients
+/// # Example usage
+/// ```rust
+/// use restful_api::types::authorization::{parse_authorization, Authorization};
+///
+/// assert_eq!(
+///     parse_authorization("Bearer v2.local.abc").unwrap(),
+///     Authorization::Bearer("v2.local.abc".to_string())
+/// );
+/// assert!(parse_authorization("Basic dXNlcjpwdw==").is_err());
+/// ```
+pub fn parse_authorization(header: &str) -> Result<Authorization, Error> {
+    let header = header.trim();
+    if header.is_empty() {
+        return Err(Error::Unauthenticated("The Authorization header is empty".to_string()));
+    }
+
+    let (scheme, credentials) = match header.split_once(char::is_whitespace) {
+        Some((scheme, credentials)) => (scheme, credentials.trim()),
+        None if is_scheme(header) => (header, ""),
+        None => return Ok(Authorization::Bearer(header.to_string())),
+    };
+    if credentials.is_empty() || credentials.contains(char::is_whitespace) {
+        return Err(Error::Unauthenticated(format!(
+            "The Authorization header must be '{} <credentials>'", scheme
+        )));
+    }
+
+    if scheme.eq_ignore_ascii_case(BEARER_SCHEME) {
+        Ok(Authorization::Bearer(credentials.to_string()))
+    } else if scheme.eq_ignore_ascii_case(API_KEY_SCHEME) {
+        Ok(Authorization::ApiKey(credentials.to_string()))
+    } else {
+        Err(Error::Unauthenticated(format!(
+            "Unsupported authorization scheme '{}', use {} or {}",
+            scheme, BEARER_SCHEME, API_KEY_SCHEME
+        )))
+    }
+}
+
+fn is_scheme(value: &str) -> bool {
+    value.eq_ignore_ascii_case(BEARER_SCHEME) || value.eq_ignore_ascii_case(API_KEY_SCHEME)
+}
+
+#[cfg(test)]
+mod authorization_test {
+    use super::{parse_authorization, Authorization};
+
+    #[test]
+    fn schemes() {
+        assert_eq!(parse_authorization("bearer  abc ").unwrap(), Authorization::Bearer("abc".to_string()));
+        assert_eq!(parse_authorization("ApiKey mk_abc").unwrap(), Authorization::ApiKey("mk_abc".to_string()));
+        assert_eq!(parse_authorization("v2.local.abc").unwrap(), Authorization::Bearer("v2.local.abc".to_string()));
+    }
+
+    #[test]
+    fn malformed_headers() {
+        for header in ["", "  ", "Bearer ", "Bearer a b", "Token abc"] {
+            assert!(parse_authorization(header).is_err(), "{}", header);
+        }
+    }
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Counters of the product read cache, as shown at `GET /admin/cache`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct CacheStats {
+    pub enabled: bool,
+    /// Product lists and products currently cached
+    pub entries: usize,
+    pub capacity: usize,
+    pub ttl_seconds: u64,
+    /// Lookups answered from the cache since startup
+    pub hits: u64,
+    /// Lookups which went to the database since startup
+    pub misses: u64,
+}
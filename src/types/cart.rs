@@ -0,0 +1,119 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use handle_errors::Error;
+
+use crate::types::money::Money;
+use crate::types::products::ProductId;
+use crate::types::validation::{Validate, Validator};
+
+/// Largest quantity of a single product in a cart
+pub const MAX_CART_QUANTITY: i32 = 999;
+
+/// A product in the cart of an account
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct CartItem {
+    pub product_id: ProductId,
+    pub name: String,
+    pub quantity: i32,
+    /// Price of the product when the quantity was last set
+    pub unit_price: Money,
+    /// Price of the product right now, `None` once the product was deleted
+    pub current_price: Option<Money>,
+    pub added_on: DateTime<Utc>,
+    pub updated_on: DateTime<Utc>,
+}
+
+impl CartItem {
+    /// Whether the product changed price or disappeared since it was put in the cart
+    pub fn is_stale(&self) -> bool {
+        self.current_price.as_ref() != Some(&self.unit_price)
+    }
+}
+
+/// The cart of an account along its totals, one per currency
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct Cart {
+    pub items: Vec<CartItem>,
+    pub totals: Vec<Money>,
+}
+
+impl Cart {
+    /// Sum the items at their current price, products no longer available are left out
+    pub fn new(items: Vec<CartItem>) -> Result<Self, Error> {
+        let mut totals: Vec<Money> = Vec::new();
+        for item in items.iter() {
+            let price = match &item.current_price {
+                Some(price) => price,
+                None => continue,
+            };
+            let subtotal = price.checked_mul(i64::from(item.quantity))?;
+            match totals.iter_mut().find(|total| total.currency == subtotal.currency) {
+                Some(total) => *total = total.checked_add(&subtotal)?,
+                None => totals.push(subtotal),
+            }
+        }
+        Ok(Cart { items, totals })
+    }
+}
+
+/// Body of `PUT /cart/items/{product_id}`
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct CartQuantity {
+    pub quantity: i32,
+}
+
+impl Validate for CartQuantity {
+    fn check(&self, validator: &mut Validator) {
+        validator.check(
+            "quantity",
+            (1..=MAX_CART_QUANTITY).contains(&self.quantity),
+            "range",
+            &format!("must be between 1 and {}", MAX_CART_QUANTITY),
+        );
+    }
+}
+
+#[cfg(test)]
+mod cart_test {
+    use super::{Cart, CartItem, CartQuantity, Money, ProductId, Utc, MAX_CART_QUANTITY};
+    use crate::types::validation::Validate;
+
+    fn item(id: i32, quantity: i32, unit_price: Money, current_price: Option<Money>) -> CartItem {
+        CartItem {
+            product_id: ProductId(id),
+            name: format!("product {}", id),
+            quantity,
+            unit_price,
+            current_price,
+            added_on: Utc::now(),
+            updated_on: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn totals_per_currency() {
+        let cart = Cart::new(vec![
+            item(1, 2, Money::new(1000, "USD"), Some(Money::new(1000, "USD"))),
+            item(2, 1, Money::new(250, "USD"), Some(Money::new(300, "USD"))),
+            item(3, 3, Money::new(500, "EUR"), Some(Money::new(500, "EUR"))),
+            item(4, 1, Money::new(900, "USD"), None),
+        ]).unwrap();
+        assert_eq!(cart.totals, vec![Money::new(2300, "USD"), Money::new(1500, "EUR")]);
+    }
+
+    #[test]
+    fn stale_items() {
+        assert!(!item(1, 1, Money::new(100, "USD"), Some(Money::new(100, "USD"))).is_stale());
+        assert!(item(1, 1, Money::new(100, "USD"), Some(Money::new(120, "USD"))).is_stale());
+        assert!(item(1, 1, Money::new(100, "USD"), None).is_stale());
+    }
+
+    #[test]
+    fn quantity_range() {
+        assert!(CartQuantity { quantity: 1 }.validate().is_ok());
+        assert!(CartQuantity { quantity: 0 }.validate().is_err());
+        assert!(CartQuantity { quantity: MAX_CART_QUANTITY + 1 }.validate().is_err());
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use handle_errors::{Error, FieldError};
+
+use crate::types::money::{Money, DEFAULT_CURRENCY};
+use crate::types::products::{normalize_tags, NewProducts, ProductTags, Products};
+use crate::types::validation::{Validate, Validator};
+
+/// Largest catalog accepted by `POST /products/import`, in bytes
+pub const MAX_IMPORT_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Formats a catalog can be imported from and exported to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CatalogFormat {
+    /// Comma separated values with a header row
+    Csv,
+    /// One JSON object per line
+    Ndjson,
+}
+
+impl CatalogFormat {
+    /// Pick the format from the `format` query parameter, falling back on the request content type
+    pub fn extract(
+        params: &HashMap<String, String>,
+        content_type: Option<&str>,
+    ) -> Result<Self, Error> {
+        let format = match params.get("format") {
+            Some(format) => format.as_str(),
+            None => match content_type.map(|c| c.split(';').next().unwrap_or_default().trim()) {
+                Some("application/x-ndjson") | Some("application/jsonl") => "ndjson",
+                _ => "csv",
+            },
+        };
+        match format {
+            "csv" => Ok(CatalogFormat::Csv),
+            "ndjson" | "jsonl" => Ok(CatalogFormat::Ndjson),
+            _ => Err(Error::ValidationError(vec![FieldError {
+                field: "format".to_string(),
+                rule: "one_of".to_string(),
+                message: "must be csv or ndjson".to_string(),
+            }])),
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            CatalogFormat::Csv => "text/csv",
+            CatalogFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+}
+
+/// A catalog line in CSV, the columns of an export can be imported back
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CsvProduct {
+    #[serde(default)]
+    pub id: Option<i32>,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub price_amount: i64,
+    #[serde(default)]
+    pub currency: Option<String>,
+    #[serde(default)]
+    pub category_id: Option<i32>,
+    /// Tags separated by `|`
+    #[serde(default)]
+    pub tags: String,
+}
+
+impl From<CsvProduct> for CatalogProduct {
+    fn from(row: CsvProduct) -> Self {
+        CatalogProduct {
+            id: row.id,
+            product: NewProducts {
+                name: row.name,
+                description: row.description,
+                price: Money::new(
+                    row.price_amount,
+                    row.currency.as_deref().unwrap_or(DEFAULT_CURRENCY),
+                ),
+            },
+            category_id: row.category_id,
+            tags: row.tags.split('|').filter(|tag| !tag.trim().is_empty()).map(String::from).collect(),
+        }
+    }
+}
+
+/// A catalog row to import, in either format. A row with the `id` of one of the seller's
+/// products replaces that product, so that an export can be edited and imported back
+#[derive(Deserialize, Debug, Clone)]
+pub struct CatalogProduct {
+    #[serde(default)]
+    pub id: Option<i32>,
+    #[serde(flatten)]
+    pub product: NewProducts,
+    #[serde(default)]
+    pub category_id: Option<i32>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl Validate for CatalogProduct {
+    fn check(&self, validator: &mut Validator) {
+        self.product.check(validator);
+        ProductTags(self.tags.clone()).check(validator);
+    }
+}
+
+impl From<&Products> for CsvProduct {
+    fn from(product: &Products) -> Self {
+        CsvProduct {
+            id: Some(product.id.0),
+            name: product.name.clone(),
+            description: product.description.clone(),
+            price_amount: product.price.amount,
+            currency: Some(product.price.currency.clone()),
+            category_id: product.category_id.as_ref().map(|category| category.0),
+            tags: product.tags.join("|"),
+        }
+    }
+}
+
+/// Header row of a CSV export, matching the fields of `CsvProduct`
+pub const CSV_HEADER: &str = "id,name,description,price_amount,currency,category_id,tags\n";
+
+/// Serialize a single product as a catalog line, CSV lines come without header
+pub fn catalog_line(format: CatalogFormat, product: &Products) -> Result<Vec<u8>, Error> {
+    match format {
+        CatalogFormat::Csv => {
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(false)
+                .from_writer(Vec::new());
+            writer
+                .serialize(CsvProduct::from(product))
+                .map_err(|e| Error::ExportError(e.to_string()))?;
+            writer
+                .into_inner()
+                .map_err(|e| Error::ExportError(e.to_string()))
+        }
+        CatalogFormat::Ndjson => {
+            let mut line = serde_json::to_vec(product)
+                .map_err(|e| Error::ExportError(e.to_string()))?;
+            line.push(b'\n');
+            Ok(line)
+        }
+    }
+}
+
+/// Errors of a single catalog row, rows are numbered from 1 and the CSV header isn't counted
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct ImportRowError {
+    pub row: usize,
+    pub errors: Vec<FieldError>,
+}
+
+impl ImportRowError {
+    /// A row referring to something which doesn't exist, such as an unknown category
+    pub fn unknown(row: usize, field: &str, message: &str) -> Self {
+        ImportRowError {
+            row,
+            errors: vec![FieldError {
+                field: field.to_string(),
+                rule: "exists".to_string(),
+                message: message.to_string(),
+            }],
+        }
+    }
+}
+
+/// Outcome of `POST /products/import`
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub total: usize,
+    pub imported: usize,
+    pub errors: Vec<ImportRowError>,
+}
+
+/// Parse and validate every row of a catalog, collecting the errors of each row.
+/// Tags come out normalized, a product id appearing twice is an error of its second row
+/// # Example usage
+/// ```rust
+/// use restful_api::types::catalog::{parse_catalog, CatalogFormat};
+///
+/// let csv = "name,price_amount\nmug,1200\n,-5\n";
+/// let (products, errors) = parse_catalog(CatalogFormat::Csv, csv.as_bytes());
+/// assert_eq!(products.len(), 1);
+/// assert_eq!(errors[0].row, 2);
+/// ```
+pub fn parse_catalog(
+    format: CatalogFormat,
+    body: &[u8],
+) -> (Vec<CatalogProduct>, Vec<ImportRowError>) {
+    let rows: Vec<Result<CatalogProduct, String>> = match format {
+        CatalogFormat::Csv => csv::Reader::from_reader(body)
+            .deserialize::<CsvProduct>()
+            .map(|row| row.map(CatalogProduct::from).map_err(|e| e.to_string()))
+            .collect(),
+        CatalogFormat::Ndjson => body
+            .split(|byte| *byte == b'\n')
+            .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+            .map(|line| serde_json::from_slice::<CatalogProduct>(line).map_err(|e| e.to_string()))
+            .collect(),
+    };
+
+    let mut products = Vec::with_capacity(rows.len());
+    let mut errors = Vec::new();
+    let mut ids = HashSet::new();
+    for (index, row) in rows.into_iter().enumerate() {
+        let row_errors = match row {
+            Ok(product) if product.id.is_some_and(|id| !ids.insert(id)) => {
+                vec![FieldError {
+                    field: "id".to_string(),
+                    rule: "unique".to_string(),
+                    message: "appears on an earlier row".to_string(),
+                }]
+            }
+            Ok(mut product) => match product.validate() {
+                Ok(_) => {
+                    product.tags = normalize_tags(&product.tags);
+                    products.push(product);
+                    continue;
+                }
+                Err(Error::ValidationError(errors)) => errors,
+                Err(e) => vec![format_error(e.to_string())],
+            },
+            Err(message) => vec![format_error(message)],
+        };
+        errors.push(ImportRowError {
+            row: index + 1,
+            errors: row_errors,
+        });
+    }
+    (products, errors)
+}
+
+fn format_error(message: String) -> FieldError {
+    FieldError {
+        field: "row".to_string(),
+        rule: "format".to_string(),
+        message,
+    }
+}
+
+#[cfg(test)]
+mod catalog_test {
+    use super::{parse_catalog, CatalogFormat, CsvProduct, HashMap, CSV_HEADER};
+
+    #[test]
+    fn format_from_content_type() {
+        let params = HashMap::new();
+        let format = CatalogFormat::extract(&params, Some("application/x-ndjson; charset=utf-8"));
+        assert_eq!(format.unwrap(), CatalogFormat::Ndjson);
+        assert_eq!(CatalogFormat::extract(&params, None).unwrap(), CatalogFormat::Csv);
+    }
+
+    #[test]
+    fn unknown_format() {
+        let mut params = HashMap::new();
+        params.insert(String::from("format"), String::from("xml"));
+        assert!(CatalogFormat::extract(&params, None).is_err());
+    }
+
+    #[test]
+    fn ndjson_rows() {
+        let body = r#"{"name": "mug", "price": {"amount": 1200, "currency": "eur"}}
+
+{"name": "cup"}
+"#;
+        let (products, errors) = parse_catalog(CatalogFormat::Ndjson, body.as_bytes());
+        assert_eq!(products.len(), 1);
+        assert_eq!(products[0].product.price.currency, "EUR");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].row, 2);
+        assert_eq!(errors[0].errors[0].rule, "format");
+    }
+
+    #[test]
+    fn csv_validation_errors() {
+        let body = "name,description,price_amount,currency\nmug,,1200,USD\n,,-1,DOLLAR\n";
+        let (products, errors) = parse_catalog(CatalogFormat::Csv, body.as_bytes());
+        assert_eq!(products.len(), 1);
+        let fields: Vec<&str> = errors[0].errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["name", "price.amount", "price.currency"]);
+    }
+
+    #[test]
+    fn csv_header_matches_fields() {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.serialize(CsvProduct {
+            id: Some(1),
+            name: "mug".to_string(),
+            description: String::new(),
+            price_amount: 1200,
+            currency: Some("USD".to_string()),
+            category_id: None,
+            tags: "kitchen|gift".to_string(),
+        }).unwrap();
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert!(output.starts_with(CSV_HEADER));
+        assert!(output.ends_with("1,mug,,1200,USD,,kitchen|gift\n"));
+    }
+
+    #[test]
+    fn exported_rows_import_back() {
+        let body = format!("{}4,mug,,1200,USD,3,Kitchen|gift\n5,cup,,900,EUR,,\n4,mug,,1200,USD,,\n", CSV_HEADER);
+        let (products, errors) = parse_catalog(CatalogFormat::Csv, body.as_bytes());
+        assert_eq!(products.len(), 2);
+        assert_eq!((products[0].id, products[0].category_id), (Some(4), Some(3)));
+        assert_eq!(products[0].tags, vec!["gift", "kitchen"]);
+        assert!(products[1].tags.is_empty());
+        assert_eq!(errors[0].row, 3);
+        assert_eq!(errors[0].errors[0].rule, "unique");
+
+        let ndjson = r#"{"id": 4, "name": "mug", "price": {"amount": 1200, "currency": "USD"}, "seller_id": 1, "category_id": 3, "tags": ["kitchen"], "version": 2}"#;
+        let (products, errors) = parse_catalog(CatalogFormat::Ndjson, ndjson.as_bytes());
+        assert!(errors.is_empty());
+        assert_eq!((products[0].id, products[0].category_id), (Some(4), Some(3)));
+        assert_eq!(products[0].tags, vec!["kitchen"]);
+    }
+}
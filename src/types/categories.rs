@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::types::validation::{Validate, Validator};
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct Category {
+    pub id: CategoryId,
+    pub name: String,
+    pub parent_id: Option<CategoryId>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct CategoryId(pub i32);
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct NewCategory {
+    pub name: String,
+    pub parent_id: Option<CategoryId>,
+}
+
+impl Validate for NewCategory {
+    fn check(&self, validator: &mut Validator) {
+        validator.length("name", &self.name, 1, 255);
+    }
+}
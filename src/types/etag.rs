@@ -0,0 +1,149 @@
+use chrono::prelude::*;
+use sha2::{Digest, Sha256};
+
+/// Entity tag of a product, its version then the version of its reviews, a quoted strong validator.
+/// The rating is part of the product so caches revalidate on reviews, while `If-Match` only
+/// checks the version, a review doesn't conflict with an edit of the seller
+/// # Example usage
+/// ```rust
+/// use restful_api::types::etag::{product_etag, IfMatch};
+///
+/// assert_eq!(product_etag(3, 5), "\"3.5\"");
+/// assert!(IfMatch::parse("\"2.5\", \"3.4\"").matches_version(3));
+/// ```
+pub fn product_etag(version: i32, reviews_version: i32) -> String {
+    format!("\"{}.{}\"", version, reviews_version)
+}
+
+/// Parsed `If-Match` header
+#[derive(Debug, PartialEq)]
+pub enum IfMatch {
+    /// `If-Match: *`, any current representation matches
+    Any,
+    /// A list of entity tags, weak tags never match
+    Tags(Vec<String>),
+}
+
+impl IfMatch {
+    pub fn parse(header: &str) -> Self {
+        if header.trim() == "*" {
+            return IfMatch::Any;
+        }
+        IfMatch::Tags(
+            header
+                .split(',')
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect(),
+        )
+    }
+
+    /// Strong comparison against the current entity tag of the resource
+    pub fn matches(&self, etag: &str) -> bool {
+        match self {
+            IfMatch::Any => true,
+            IfMatch::Tags(tags) => tags.iter().any(|tag| !tag.starts_with("W/") && tag == etag),
+        }
+    }
+
+    /// Strong comparison of the version in product entity tags, the version of the reviews left out
+    pub fn matches_version(&self, version: i32) -> bool {
+        match self {
+            IfMatch::Any => true,
+            IfMatch::Tags(tags) => tags.iter().any(|tag| {
+                tag.strip_prefix('"')
+                    .and_then(|tag| tag.strip_suffix('"'))
+                    .and_then(|tag| tag.split('.').next())
+                    .and_then(|tagged| tagged.parse::<i32>().ok())
+                    == Some(version)
+            }),
+        }
+    }
+}
+
+/// Entity tag of a representation without version, derived from its bytes
+pub fn content_etag(body: &[u8]) -> String {
+    format!("\"{}\"", &hex::encode(Sha256::digest(body))[..32])
+}
+
+/// Parsed `If-None-Match` header
+#[derive(Debug, PartialEq)]
+pub enum IfNoneMatch {
+    /// `If-None-Match: *`, any current representation matches
+    Any,
+    /// A list of entity tags, weak or not
+    Tags(Vec<String>),
+}
+
+impl IfNoneMatch {
+    pub fn parse(header: &str) -> Self {
+        match IfMatch::parse(header) {
+            IfMatch::Any => IfNoneMatch::Any,
+            IfMatch::Tags(tags) => IfNoneMatch::Tags(tags),
+        }
+    }
+
+    /// Weak comparison against the current entity tag, as caches revalidate with it
+    pub fn matches(&self, etag: &str) -> bool {
+        let opaque = |tag: &str| tag.trim_start_matches("W/").to_string();
+        match self {
+            IfNoneMatch::Any => true,
+            IfNoneMatch::Tags(tags) => tags.iter().any(|tag| opaque(tag) == opaque(etag)),
+        }
+    }
+}
+
+/// Date in the format of `Last-Modified` and `If-Modified-Since`, `Sun, 06 Nov 1994 08:49:37 GMT`
+pub fn http_date(date: &DateTime<Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+pub fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value.trim()).ok().map(|date| date.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod etag_test {
+    use super::{content_etag, http_date, parse_http_date, product_etag, IfMatch, IfNoneMatch};
+
+    #[test]
+    fn any_matches() {
+        assert_eq!(IfMatch::parse(" * "), IfMatch::Any);
+        assert!(IfMatch::Any.matches(&product_etag(7, 1)));
+        assert!(IfMatch::Any.matches_version(7));
+    }
+
+    #[test]
+    fn weak_tags_never_match() {
+        assert!(!IfMatch::parse("W/\"7.1\"").matches(&product_etag(7, 1)));
+        assert!(!IfMatch::parse("W/\"7.1\"").matches_version(7));
+    }
+
+    #[test]
+    fn stale_tag() {
+        assert!(!IfMatch::parse("\"6.1\"").matches_version(7));
+    }
+
+    #[test]
+    fn reviews_dont_conflict() {
+        assert!(IfMatch::parse(&product_etag(7, 1)).matches_version(7));
+        assert!(IfMatch::parse("\"7\"").matches_version(7));
+        assert!(!IfMatch::parse("\"70.1\"").matches_version(7));
+    }
+
+    #[test]
+    fn if_none_match_is_weak() {
+        let etag = content_etag(b"[]");
+        assert_eq!(etag.len(), 34);
+        assert!(IfNoneMatch::parse(&format!("\"1\", W/{}", etag)).matches(&etag));
+        assert!(IfNoneMatch::parse("*").matches(&product_etag(7, 1)));
+        assert!(!IfNoneMatch::parse("\"7.1\"").matches(&product_etag(7, 2)));
+    }
+
+    #[test]
+    fn http_dates() {
+        let date = parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+        assert_eq!(http_date(&date), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert!(parse_http_date("yesterday").is_none());
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use handle_errors::Error;
+
+use crate::types::accounts::AccountId;
+use crate::types::audit::AuditAction;
+use crate::types::products::{ProductId, Products};
+
+/// What happened to a product, the `event` of a Server-Sent Event
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProductEventKind {
+    /// Added to the catalog, by hand, by an import or by restoring it
+    Created,
+    Updated,
+    /// Soft deleted or purged
+    Deleted,
+}
+
+impl ProductEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProductEventKind::Created => "created",
+            ProductEventKind::Updated => "updated",
+            ProductEventKind::Deleted => "deleted",
+        }
+    }
+}
+
+/// A change of a product, as pushed to the subscribers of `GET /products/stream`
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct ProductEvent {
+    pub kind: ProductEventKind,
+    pub product_id: ProductId,
+    pub seller_id: Option<AccountId>,
+    /// The product after the change, `None` once deleted
+    pub product: Option<Products>,
+    pub occurred_on: DateTime<Utc>,
+}
+
+impl ProductEvent {
+    pub fn created(product: &Products) -> Self {
+        ProductEvent::changed(ProductEventKind::Created, product)
+    }
+
+    pub fn updated(product: &Products) -> Self {
+        ProductEvent::changed(ProductEventKind::Updated, product)
+    }
+
+    fn changed(kind: ProductEventKind, product: &Products) -> Self {
+        ProductEvent {
+            kind,
+            product_id: product.id.clone(),
+            seller_id: product.seller_id.clone(),
+            product: Some(product.clone()),
+            occurred_on: Utc::now(),
+        }
+    }
+
+    pub fn deleted(id: i32, seller_id: Option<AccountId>) -> Self {
+        ProductEvent {
+            kind: ProductEventKind::Deleted,
+            product_id: ProductId(id),
+            seller_id,
+            product: None,
+            occurred_on: Utc::now(),
+        }
+    }
+}
+
+/// A committed change, sent to the other instances over Postgres notifications.
+/// It only carries ids, a notification payload can't exceed 8000 bytes
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum StoreChange {
+    Product {
+        kind: ProductEventKind,
+        product_id: i32,
+        seller_id: Option<i32>,
+    },
+    /// The reviews of a product changed, and so did its rating
+    Reviews { product_id: i32 },
+    /// Categories changed, which may affect any product
+    Categories,
+    /// A seller imported a catalog, a single change however many products it created
+    Catalog { seller_id: i32 },
+    /// `action` is the audit action of the change, such as `account.verify_email`.
+    /// Instances keep no account in memory, they only log the changes of the others
+    Account { account_id: i32, action: String },
+}
+
+impl StoreChange {
+    pub fn product(kind: ProductEventKind, id: i32, seller_id: Option<&AccountId>) -> Self {
+        StoreChange::Product {
+            kind,
+            product_id: id,
+            seller_id: seller_id.map(|seller| seller.0),
+        }
+    }
+
+    pub fn account(account_id: &AccountId, action: AuditAction) -> Self {
+        StoreChange::Account {
+            account_id: account_id.0,
+            action: action.as_str().to_string(),
+        }
+    }
+}
+
+/// Events a subscriber wants, every event when empty
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct EventFilter {
+    pub seller_id: Option<i32>,
+    pub product_id: Option<i32>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &ProductEvent) -> bool {
+        let seller = self
+            .seller_id
+            .map_or(true, |seller| event.seller_id.as_ref().is_some_and(|id| id.0 == seller));
+        let product = self.product_id.map_or(true, |product| event.product_id.0 == product);
+        seller && product
+    }
+}
+
+/// Extract the seller and product to follow from the query parameters
+/// # Example query
+/// GET requests to this route can take `seller` and `product` parameters
+/// `/products/stream?seller=2&product=7`
+pub fn extract_event_filter(params: &HashMap<String, String>) -> Result<EventFilter, Error> {
+    let parse = |name: &str| {
+        params
+            .get(name)
+            .map(|value| value.parse::<i32>())
+            .transpose()
+            .map_err(Error::ParseError)
+    };
+
+    Ok(EventFilter {
+        seller_id: parse("seller")?,
+        product_id: parse("product")?,
+    })
+}
+
+#[cfg(test)]
+mod events_test {
+    use super::{extract_event_filter, EventFilter, HashMap, ProductEvent, ProductEventKind, StoreChange};
+    use crate::types::accounts::AccountId;
+
+    #[test]
+    fn filter_by_seller_and_product() {
+        let mut params = HashMap::new();
+        params.insert(String::from("seller"), String::from("2"));
+        let filter = extract_event_filter(&params).unwrap();
+        assert_eq!(filter, EventFilter { seller_id: Some(2), product_id: None });
+
+        assert!(filter.matches(&ProductEvent::deleted(7, Some(AccountId(2)))));
+        assert!(!filter.matches(&ProductEvent::deleted(7, Some(AccountId(3)))));
+        assert!(!filter.matches(&ProductEvent::deleted(7, None)));
+
+        let filter = EventFilter { seller_id: Some(2), product_id: Some(8) };
+        assert!(!filter.matches(&ProductEvent::deleted(7, Some(AccountId(2)))));
+        assert!(EventFilter::default().matches(&ProductEvent::deleted(7, None)));
+    }
+
+    #[test]
+    fn changes_fit_in_a_notification() {
+        let change = StoreChange::product(ProductEventKind::Updated, 7, Some(&AccountId(2)));
+        let json = serde_json::to_string(&change).unwrap();
+        assert_eq!(json, r#"{"change":"product","kind":"updated","product_id":7,"seller_id":2}"#);
+        assert_eq!(serde_json::from_str::<StoreChange>(&json).unwrap(), change);
+        assert_eq!(serde_json::to_string(&StoreChange::Categories).unwrap(), r#"{"change":"categories"}"#);
+        let json = serde_json::to_string(&StoreChange::Catalog { seller_id: 2 }).unwrap();
+        assert_eq!(json, r#"{"change":"catalog","seller_id":2}"#);
+    }
+
+    #[test]
+    fn unparsable_filter() {
+        let mut params = HashMap::new();
+        params.insert(String::from("product"), String::from("mug"));
+        assert!(extract_event_filter(&params).is_err());
+    }
+}
@@ -0,0 +1,58 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Header carrying the client chosen key of a retryable request
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// Header added to a response replayed from a previous request
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+/// Response headers kept along a stored response
+pub const REPLAYED_HEADERS: [&str; 2] = ["content-type", "etag"];
+/// Keys are forgotten after this many hours, the request is then processed again
+pub const IDEMPOTENCY_KEY_TTL_HOURS: i32 = 24;
+/// A request still in progress after this many seconds is deemed lost, say by a crash of the
+/// instance processing it, and its key can be claimed again
+pub const IDEMPOTENCY_LEASE_SECONDS: i32 = 300;
+
+/// Response saved for a key, replayed to every retry of the same request
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredResponse {
+    pub status_code: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// Outcome of claiming an idempotency key for a request
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdempotencyState {
+    /// First time the key is seen, the request has to be processed
+    New,
+    /// The same request already completed, its response is replayed
+    Completed(StoredResponse),
+    /// The same request is still being processed
+    InProgress,
+    /// The key was used for a different request
+    Mismatch,
+}
+
+/// Fingerprint of a request, so that a key reused with another request can be told apart
+/// # Example usage
+/// ```rust
+/// use restful_api::types::idempotency::request_hash;
+///
+/// let first = request_hash("POST /products", &serde_json::json!({"name": "a"}));
+/// let second = request_hash("POST /products", &serde_json::json!({"name": "b"}));
+/// assert_eq!(first.len(), 64);
+/// assert_ne!(first, second);
+/// ```
+pub fn request_hash<T: Serialize>(route: &str, body: &T) -> String {
+    raw_request_hash(route, &serde_json::to_vec(body).unwrap_or_default())
+}
+
+/// Fingerprint of a request whose body isn't deserialized, such as an uploaded file
+pub fn raw_request_hash(route: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(route.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(body);
+    hex::encode(hasher.finalize())
+}
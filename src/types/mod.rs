@@ -1,3 +1,20 @@
 pub mod accounts;
+pub mod account_tokens;
 pub mod products;
-pub mod pagination;
\ No newline at end of file
+pub mod pagination;
+pub mod categories;
+pub mod money;
+pub mod validation;
+pub mod audit;
+pub mod etag;
+pub mod idempotency;
+pub mod catalog;
+pub mod cart;
+pub mod sellers;
+pub mod reviews;
+pub mod search;
+pub mod two_factor;
+pub mod api_keys;
+pub mod authorization;
+pub mod cache;
+pub mod events;
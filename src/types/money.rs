@@ -0,0 +1,175 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use utoipa::ToSchema;
+
+use handle_errors::Error;
+
+use crate::types::validation::{Validate, Validator};
+
+/// Currency used when a price is given without one
+pub const DEFAULT_CURRENCY: &str = "USD";
+
+/// An exact amount of money, stored in the minor unit of its currency
+/// # Example
+/// ```rust
+/// use restful_api::types::money::Money;
+///
+/// let price = Money::new(1050, "usd");
+/// assert_eq!(price.amount, 1050);
+/// assert_eq!(price.currency, "USD");
+/// assert_eq!(price.checked_mul(3).unwrap().amount, 3150);
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct Money {
+    /// Amount in the minor unit of the currency, e.g. cents for USD
+    pub amount: i64,
+    /// ISO 4217 currency code
+    #[serde(default = "default_currency", deserialize_with = "uppercase")]
+    pub currency: String,
+}
+
+fn default_currency() -> String {
+    DEFAULT_CURRENCY.to_string()
+}
+
+fn uppercase<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    String::deserialize(deserializer).map(|code| code.to_uppercase())
+}
+
+impl Money {
+    pub fn new(amount: i64, currency: &str) -> Self {
+        Money {
+            amount,
+            currency: currency.to_uppercase(),
+        }
+    }
+
+    /// Zero in the given currency, the starting point of a sum
+    pub fn zero(currency: &str) -> Self {
+        Money::new(0, currency)
+    }
+
+    /// Add two amounts of the same currency, failing on overflow or currency mismatch
+    pub fn checked_add(&self, other: &Money) -> Result<Money, Error> {
+        if self.currency != other.currency {
+            return Err(Error::InvalidMoney(format!(
+                "cannot add {} to {}",
+                other.currency, self.currency
+            )));
+        }
+        self.amount
+            .checked_add(other.amount)
+            .map(|amount| Money::new(amount, &self.currency))
+            .ok_or_else(|| Error::InvalidMoney("amount overflow".to_string()))
+    }
+
+    /// Multiply an amount by a quantity, failing on overflow
+    pub fn checked_mul(&self, quantity: i64) -> Result<Money, Error> {
+        self.amount
+            .checked_mul(quantity)
+            .map(|amount| Money::new(amount, &self.currency))
+            .ok_or_else(|| Error::InvalidMoney("amount overflow".to_string()))
+    }
+}
+
+impl Validate for Money {
+    /// Reject negative amounts and anything which doesn't look like an ISO 4217 code
+    fn check(&self, validator: &mut Validator) {
+        validator
+            .check("amount", self.amount >= 0, "min", "must not be negative")
+            .check(
+                "currency",
+                is_currency_code(&self.currency),
+                "currency",
+                "must be an ISO 4217 currency code",
+            );
+    }
+}
+
+/// A new amount for a stored price, the currency is kept as it is when left out
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct MoneyUpdate {
+    /// Amount in the minor unit of the currency
+    pub amount: i64,
+    /// ISO 4217 currency code, the stored one when missing
+    #[serde(default, deserialize_with = "uppercase_optional")]
+    pub currency: Option<String>,
+}
+
+fn uppercase_optional<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Option::<String>::deserialize(deserializer).map(|code| code.map(|code| code.to_uppercase()))
+}
+
+impl Validate for MoneyUpdate {
+    fn check(&self, validator: &mut Validator) {
+        validator.check("amount", self.amount >= 0, "min", "must not be negative");
+        if let Some(currency) = &self.currency {
+            validator.check(
+                "currency",
+                is_currency_code(currency),
+                "currency",
+                "must be an ISO 4217 currency code",
+            );
+        }
+    }
+}
+
+/// Check the shape of a currency code, three uppercase ASCII letters
+pub fn is_currency_code(code: &str) -> bool {
+    code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase())
+}
+
+#[cfg(test)]
+mod money_test {
+    use super::{Money, MoneyUpdate};
+    use crate::types::validation::Validate;
+
+    #[test]
+    fn negative_amount() {
+        let result = Money::new(-1, "USD").validate().unwrap_err();
+        assert_eq!(format!("{}", result), "Invalid fields: amount");
+    }
+
+    #[test]
+    fn invalid_currency() {
+        assert!(Money::new(100, "DOLLAR").validate().is_err());
+        assert!(Money::new(100, "U$D").validate().is_err());
+        assert!(Money::new(100, "eur").validate().is_ok());
+    }
+
+    #[test]
+    fn add_same_currency() {
+        let total = Money::new(1050, "USD").checked_add(&Money::new(25, "USD")).unwrap();
+        assert_eq!(total, Money::new(1075, "USD"));
+    }
+
+    #[test]
+    fn add_different_currency() {
+        assert!(Money::new(1050, "USD").checked_add(&Money::new(25, "EUR")).is_err());
+    }
+
+    #[test]
+    fn multiply_overflow() {
+        assert!(Money::new(i64::MAX, "USD").checked_mul(2).is_err());
+    }
+
+    #[test]
+    fn deserialize_without_currency() {
+        let price: Money = serde_json::from_str(r#"{"amount": 250}"#).unwrap();
+        assert_eq!(price, Money::new(250, "USD"));
+    }
+
+    #[test]
+    fn deserialize_lowercase_currency() {
+        let price: Money = serde_json::from_str(r#"{"amount": 250, "currency": "eur"}"#).unwrap();
+        assert_eq!(price, Money::new(250, "EUR"));
+    }
+
+    #[test]
+    fn update_keeps_missing_currency() {
+        let price: MoneyUpdate = serde_json::from_str(r#"{"amount": 500}"#).unwrap();
+        assert_eq!(price, MoneyUpdate { amount: 500, currency: None });
+        let price: MoneyUpdate = serde_json::from_str(r#"{"amount": 500, "currency": "eur"}"#).unwrap();
+        assert_eq!(price.currency.as_deref(), Some("EUR"));
+        assert!(MoneyUpdate { amount: 500, currency: Some("E".to_string()) }.validate().is_err());
+    }
+}
@@ -21,6 +21,7 @@ pub struct Pagination {
 /// # Example usage
 /// ```rust
 /// use std::collections::HashMap;
+/// use restful_api::types::pagination;
 ///
 /// let mut query = HashMap::new();
 /// query.insert("limit".to_string(), "1".to_string());
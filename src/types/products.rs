@@ -1,17 +1,273 @@
+use std::collections::HashMap;
+
+use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use handle_errors::{Error, FieldError};
+
+use crate::types::accounts::AccountId;
+use crate::types::categories::CategoryId;
+use crate::types::money::{Money, MoneyUpdate};
+use crate::types::validation::{Validate, Validator};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Longest product description accepted, in characters
+pub const MAX_DESCRIPTION_LENGTH: usize = 10_000;
+/// Most tags a single product can carry
+pub const MAX_TAGS: usize = 20;
+
+/// A product as handed out by the store and sent to clients
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct Products {
     pub id: ProductId,
     pub name: String,
-    pub price: i32,
+    pub description: String,
+    pub price: Money,
+    pub seller_id: Option<AccountId>,
+    pub category_id: Option<CategoryId>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Mean of the review ratings, `None` until the product is reviewed
+    #[serde(default)]
+    pub average_rating: Option<f64>,
+    #[serde(default)]
+    pub review_count: i64,
+    pub created_on: DateTime<Utc>,
+    pub updated_on: DateTime<Utc>,
+    /// Bumped on every change of the seller, checked by `If-Match` when updating the product
+    pub version: i32,
+    /// Bumped when the reviews change, the `ETag` of the product is derived from both versions
+    pub reviews_version: i32,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// A row of the `products` table, bookkeeping columns such as `deleted_at` included.
+/// It is turned into a `Products` before leaving the store
+#[derive(Debug, Clone)]
+pub struct ProductRecord {
+    pub id: ProductId,
+    pub name: String,
+    pub description: String,
+    pub price_amount: i64,
+    pub currency: String,
+    pub seller_id: Option<AccountId>,
+    pub category_id: Option<CategoryId>,
+    pub tags: Vec<String>,
+    pub average_rating: Option<f64>,
+    pub review_count: i64,
+    pub created_on: DateTime<Utc>,
+    pub updated_on: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub version: i32,
+    pub reviews_version: i32,
+}
+
+impl From<ProductRecord> for Products {
+    fn from(record: ProductRecord) -> Self {
+        Products {
+            id: record.id,
+            name: record.name,
+            description: record.description,
+            price: Money {
+                amount: record.price_amount,
+                currency: record.currency,
+            },
+            seller_id: record.seller_id,
+            category_id: record.category_id,
+            tags: record.tags,
+            average_rating: record.average_rating,
+            review_count: record.review_count,
+            created_on: record.created_on,
+            updated_on: record.updated_on,
+            version: record.version,
+            reviews_version: record.reviews_version,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct ProductId(pub i32);
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct NewProducts {
     pub name: String,
-    pub price: i32,
-}
\ No newline at end of file
+    #[serde(default)]
+    pub description: String,
+    pub price: Money,
+}
+
+impl Validate for NewProducts {
+    fn check(&self, validator: &mut Validator) {
+        validator
+            .length("name", &self.name, 1, 255)
+            .length("description", &self.description, 0, MAX_DESCRIPTION_LENGTH)
+            .nested("price", &self.price);
+    }
+}
+
+/// Body of `PUT`/`PATCH /products/{id}`, only the given fields are changed
+#[derive(Serialize, Deserialize, Debug, Clone, Default, ToSchema)]
+pub struct UpdateProduct {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub price: Option<MoneyUpdate>,
+}
+
+impl Validate for UpdateProduct {
+    fn check(&self, validator: &mut Validator) {
+        if let Some(name) = &self.name {
+            validator.length("name", name, 1, 255);
+        }
+        if let Some(description) = &self.description {
+            validator.length("description", description, 0, MAX_DESCRIPTION_LENGTH);
+        }
+        validator.nested("price", &self.price);
+    }
+}
+
+/// Body of `PUT /products/{id}/category`, a `null` category detaches the product
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct ProductCategory {
+    pub category_id: Option<CategoryId>,
+}
+
+impl Validate for ProductCategory {
+    fn check(&self, _validator: &mut Validator) {}
+}
+
+/// Body of `PUT /products/{id}/tags`, a plain JSON array of tag names
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[serde(transparent)]
+pub struct ProductTags(pub Vec<String>);
+
+impl Validate for ProductTags {
+    fn check(&self, validator: &mut Validator) {
+        validator.check(
+            "tags",
+            self.0.len() <= MAX_TAGS,
+            "count",
+            &format!("must contain at most {} tags", MAX_TAGS),
+        );
+        for (index, tag) in self.0.iter().enumerate() {
+            validator.length(&format!("tags[{}]", index), tag, 1, 64);
+        }
+    }
+}
+
+/// Filters which can be attached to `GET /products`
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ProductFilter {
+    /// Only return products in this category or one of its descendants
+    pub category_id: Option<i32>,
+    /// Only return products carrying this tag
+    pub tag: Option<String>,
+    /// Only return products sold by this account
+    pub seller_id: Option<i32>,
+    /// Order of the returned products
+    pub sort: ProductSort,
+}
+
+/// Orders in which `GET /products` can return products
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProductSort {
+    /// Oldest products first
+    #[default]
+    Id,
+    /// Best rated products first, products without reviews last
+    Rating,
+}
+
+/// Extract the product filters from the `/products` query parameters
+/// # Example query
+/// `/products?category=3&tag=handmade&seller=7&sort=rating`
+pub fn extract_product_filter(
+    params: &HashMap<String, String>,
+) -> Result<ProductFilter, Error> {
+    let category_id = params
+        .get("category")
+        .map(|category| category.parse())
+        .transpose()
+        .map_err(Error::ParseError)?;
+    let seller_id = params
+        .get("seller")
+        .map(|seller| seller.parse())
+        .transpose()
+        .map_err(Error::ParseError)?;
+    let sort = match params.get("sort").map(String::as_str) {
+        None | Some("id") => ProductSort::Id,
+        Some("rating") => ProductSort::Rating,
+        Some(_) => {
+            return Err(Error::ValidationError(vec![FieldError {
+                field: "sort".to_string(),
+                rule: "one_of".to_string(),
+                message: "must be id or rating".to_string(),
+            }]))
+        }
+    };
+
+    Ok(ProductFilter {
+        category_id,
+        tag: params.get("tag").map(|tag| normalize_tag(tag)),
+        seller_id,
+        sort,
+    })
+}
+
+/// Tags are stored lowercase and trimmed so that `Handmade ` and `handmade` are the same tag
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+/// Normalize a list of tags, dropping the blank ones and the duplicates
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut tags: Vec<String> = tags
+        .iter()
+        .map(|tag| normalize_tag(tag))
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+#[cfg(test)]
+mod products_test {
+    use super::{extract_product_filter, HashMap, ProductFilter, ProductSort};
+
+    #[test]
+    fn empty_filter() {
+        let params = HashMap::new();
+        assert_eq!(extract_product_filter(&params).unwrap(), ProductFilter::default());
+    }
+
+    #[test]
+    fn category_and_tag_filter() {
+        let mut params = HashMap::new();
+        params.insert(String::from("category"), String::from("3"));
+        params.insert(String::from("tag"), String::from(" Handmade"));
+        params.insert(String::from("seller"), String::from("7"));
+        params.insert(String::from("sort"), String::from("rating"));
+        let expected = ProductFilter {
+            category_id: Some(3),
+            tag: Some(String::from("handmade")),
+            seller_id: Some(7),
+            sort: ProductSort::Rating,
+        };
+        assert_eq!(extract_product_filter(&params).unwrap(), expected);
+    }
+
+    #[test]
+    fn unknown_sort() {
+        let mut params = HashMap::new();
+        params.insert(String::from("sort"), String::from("price"));
+        let result = format!("{}", extract_product_filter(&params).unwrap_err());
+        assert_eq!(result, "Invalid fields: sort");
+    }
+
+    #[test]
+    fn wrong_category_type() {
+        let mut params = HashMap::new();
+        params.insert(String::from("category"), String::from("NOT_A_NUMBER"));
+        let result = format!("{}", extract_product_filter(&params).unwrap_err());
+        assert_eq!(result, "Cannot parse parameter: invalid digit found in string");
+    }
+}
@@ -0,0 +1,61 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::types::accounts::AccountId;
+use crate::types::products::ProductId;
+use crate::types::validation::{Validate, Validator};
+
+/// Longest review text accepted, in characters
+pub const MAX_REVIEW_LENGTH: usize = 5_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct Review {
+    pub id: ReviewId,
+    pub product_id: ProductId,
+    pub author_id: AccountId,
+    /// From 1 to 5 stars
+    pub rating: i16,
+    pub body: String,
+    pub created_on: DateTime<Utc>,
+    pub updated_on: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct ReviewId(pub i32);
+
+/// Body of `POST /products/{id}/reviews` and `PUT /reviews/{id}`
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct NewReview {
+    pub rating: i16,
+    #[serde(default)]
+    pub body: String,
+}
+
+impl Validate for NewReview {
+    fn check(&self, validator: &mut Validator) {
+        validator
+            .check("rating", (1..=5).contains(&self.rating), "range", "must be between 1 and 5")
+            .check(
+                "body",
+                self.body.chars().count() <= MAX_REVIEW_LENGTH,
+                "length",
+                &format!("must be at most {} characters long", MAX_REVIEW_LENGTH),
+            );
+    }
+}
+
+#[cfg(test)]
+mod reviews_test {
+    use super::NewReview;
+    use crate::types::validation::Validate;
+
+    #[test]
+    fn rating_range() {
+        for rating in 1..=5 {
+            assert!(NewReview { rating, body: String::new() }.validate().is_ok());
+        }
+        assert!(NewReview { rating: 0, body: String::new() }.validate().is_err());
+        assert!(NewReview { rating: 6, body: String::new() }.validate().is_err());
+    }
+}
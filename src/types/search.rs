@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use handle_errors::Error;
+
+use crate::types::products::Products;
+use crate::types::validation::Validator;
+
+/// Longest search query accepted, in characters
+pub const MAX_SEARCH_QUERY_LENGTH: usize = 200;
+/// Control characters marking the matches in a raw headline, they are stripped from the searched text
+pub const MATCH_START: char = '\u{2}';
+pub const MATCH_END: char = '\u{3}';
+
+/// A product matching a search, best matches come first
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct SearchResult {
+    #[serde(flatten)]
+    pub product: Products,
+    /// Relevance of the product for the query, only meaningful within one search
+    pub rank: f32,
+    /// Excerpt of the name and description with the matching words wrapped in `<mark>`
+    pub snippet: String,
+}
+
+/// Extract the search terms from the `/products/search` query parameters.
+/// The terms follow the web search syntax: `"exact phrase"`, `or` and `-excluded`
+/// # Example query
+/// `/products/search?q=ceramic mug -plastic&limit=10&offset=0`
+pub fn extract_search_query(params: &HashMap<String, String>) -> Result<String, Error> {
+    let query = params.get("q").map(|q| q.trim()).unwrap_or_default();
+    let mut validator = Validator::default();
+    validator.length("q", query, 1, MAX_SEARCH_QUERY_LENGTH);
+    validator.finish().map(|_| query.to_string())
+}
+
+/// Escape a raw headline as HTML and wrap its matches in `<mark>`, product texts are user input
+pub fn highlight(headline: &str) -> String {
+    let mut html = String::with_capacity(headline.len());
+    for c in headline.chars() {
+        match c {
+            MATCH_START => html.push_str("<mark>"),
+            MATCH_END => html.push_str("</mark>"),
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+    html
+}
+
+#[cfg(test)]
+mod search_test {
+    use super::{extract_search_query, highlight, HashMap};
+
+    #[test]
+    fn escaped_snippet() {
+        let headline = "<b>Big</b> \u{2}mug\u{3} & \"cup\"";
+        assert_eq!(highlight(headline), "&lt;b&gt;Big&lt;/b&gt; <mark>mug</mark> &amp; &quot;cup&quot;");
+    }
+
+    #[test]
+    fn search_query() {
+        let mut params = HashMap::new();
+        params.insert(String::from("q"), String::from(" ceramic mug "));
+        assert_eq!(extract_search_query(&params).unwrap(), "ceramic mug");
+    }
+
+    #[test]
+    fn missing_search_query() {
+        let mut params = HashMap::new();
+        assert!(extract_search_query(&params).is_err());
+        params.insert(String::from("q"), String::from("  "));
+        let result = format!("{}", extract_search_query(&params).unwrap_err());
+        assert_eq!(result, "Invalid fields: q");
+    }
+}
@@ -0,0 +1,61 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::types::accounts::AccountId;
+use crate::types::validation::{Validate, Validator};
+
+/// Longest bio a seller can write on their profile
+pub const MAX_BIO_LENGTH: usize = 2_000;
+
+/// Public profile of an account, as shown on its storefront
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct SellerProfile {
+    pub id: AccountId,
+    /// Name picked by the seller, `Seller {id}` until one is set
+    pub display_name: String,
+    pub bio: String,
+    pub joined_on: DateTime<Utc>,
+    /// Number of products currently listed
+    pub product_count: i64,
+}
+
+/// Body of `PATCH /sellers/me`, fields left out are kept
+#[derive(Serialize, Deserialize, Debug, Clone, Default, ToSchema)]
+pub struct UpdateSellerProfile {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+}
+
+impl Validate for UpdateSellerProfile {
+    fn check(&self, validator: &mut Validator) {
+        if let Some(display_name) = &self.display_name {
+            validator.length("display_name", display_name, 1, 100);
+        }
+        if let Some(bio) = &self.bio {
+            validator.check(
+                "bio",
+                bio.chars().count() <= MAX_BIO_LENGTH,
+                "length",
+                &format!("must be at most {} characters long", MAX_BIO_LENGTH),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod sellers_test {
+    use super::{UpdateSellerProfile, MAX_BIO_LENGTH};
+    use crate::types::validation::Validate;
+
+    #[test]
+    fn partial_update() {
+        assert!(UpdateSellerProfile::default().validate().is_ok());
+        let update = UpdateSellerProfile {
+            display_name: Some(" ".to_string()),
+            bio: Some("b".repeat(MAX_BIO_LENGTH + 1)),
+        };
+        let result = update.validate().unwrap_err();
+        assert_eq!(format!("{}", result), "Invalid fields: display_name, bio");
+    }
+}
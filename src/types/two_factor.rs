@@ -0,0 +1,216 @@
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use utoipa::ToSchema;
+
+use crate::types::account_tokens::hash_token;
+use crate::types::validation::{Validate, Validator};
+
+/// Digits of an authentication code
+pub const TOTP_DIGITS: u32 = 6;
+/// Seconds an authentication code stays current
+pub const TOTP_PERIOD: i64 = 30;
+/// Steps accepted before and after the current one, covering clock drift of the device
+pub const TOTP_WINDOW: i64 = 1;
+/// Name shown next to the account in authenticator apps
+pub const TOTP_ISSUER: &str = "Marketplace";
+/// Backup codes handed out when two-factor authentication is enabled
+pub const BACKUP_CODE_COUNT: usize = 10;
+
+/// Random secret shared with the authenticator app, base32 encoded as the apps expect it
+pub fn generate_secret() -> String {
+    BASE32_NOPAD.encode(&rand::thread_rng().gen::<[u8; 20]>())
+}
+
+/// Code of a time step as defined by RFC 6238, HMAC-SHA1 with dynamic truncation
+pub fn totp_code(secret: &[u8], step: i64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    format!("{:0width$}", binary % 10u32.pow(TOTP_DIGITS), width = TOTP_DIGITS as usize)
+}
+
+/// Check a code against the steps around `now`, a unix timestamp.
+/// Returns the matched step, so that the caller can refuse to accept it twice
+/// # Example usage
+/// ```rust
+/// use restful_api::types::two_factor::{totp_code, verify_totp, TOTP_PERIOD};
+///
+/// let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+/// let code = totp_code(b"12345678901234567890", 59 / TOTP_PERIOD);
+/// assert_eq!(verify_totp(secret, &code, 59), Some(1));
+/// assert_eq!(verify_totp(secret, "000000", 59), None);
+/// ```
+pub fn verify_totp(secret: &str, code: &str, now: i64) -> Option<i64> {
+    let secret = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    let code = code.trim();
+    let current = now / TOTP_PERIOD;
+    (current - TOTP_WINDOW..=current + TOTP_WINDOW).find(|step| totp_code(&secret, *step) == code)
+}
+
+/// A 6 digit code comes from the authenticator app, anything else is taken as a backup code
+pub fn is_totp_code(code: &str) -> bool {
+    let code = code.trim();
+    code.len() == TOTP_DIGITS as usize && code.chars().all(|c| c.is_ascii_digit())
+}
+
+/// URI scanned by authenticator apps, usually shown as a QR code
+pub fn otpauth_uri(username: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(TOTP_ISSUER),
+        percent_encode(username),
+        secret,
+        percent_encode(TOTP_ISSUER),
+        TOTP_DIGITS,
+        TOTP_PERIOD
+    )
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Single use codes for when the authenticator app is lost, formatted `xxxx-xxxx`
+pub fn generate_backup_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    (0..BACKUP_CODE_COUNT)
+        .map(|_| {
+            let code = hex::encode(rng.gen::<[u8; 4]>());
+            format!("{}-{}", &code[..4], &code[4..])
+        })
+        .collect()
+}
+
+/// Hash under which a backup code is stored, the dash and the case don't matter
+pub fn hash_backup_code(code: &str) -> String {
+    let code: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect();
+    hash_token(&code.to_lowercase())
+}
+
+/// Body of `POST /account/2fa/confirm`
+#[derive(Deserialize, Clone, ToSchema)]
+pub struct TotpCode {
+    pub code: String,
+}
+
+impl Validate for TotpCode {
+    fn check(&self, validator: &mut Validator) {
+        validator.check("code", is_totp_code(&self.code), "format", "must be a 6 digit code");
+    }
+}
+
+/// Body of `POST /login/2fa`, the code is either from the authenticator app or a backup code
+#[derive(Deserialize, Clone, ToSchema)]
+pub struct TwoFactorLogin {
+    pub challenge_token: String,
+    pub code: String,
+}
+
+impl Validate for TwoFactorLogin {
+    fn check(&self, validator: &mut Validator) {
+        validator
+            .length("challenge_token", &self.challenge_token, 1, 128)
+            .length("code", &self.code, 1, 32);
+    }
+}
+
+/// Body of `POST /account/2fa/disable`, the session alone isn't enough to turn protection off
+#[derive(Deserialize, Clone, ToSchema)]
+pub struct DisableTwoFactor {
+    pub password: String,
+    pub code: String,
+}
+
+impl Validate for DisableTwoFactor {
+    fn check(&self, validator: &mut Validator) {
+        validator
+            .length("password", &self.password, 1, 128)
+            .length("code", &self.code, 1, 32);
+    }
+}
+
+/// Reply of `POST /account/2fa/enroll`, the secret is only shown until enrollment is confirmed
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+/// Reply of `POST /account/2fa/confirm`, the codes are never shown again
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct BackupCodes {
+    pub backup_codes: Vec<String>,
+}
+
+/// Reply of `POST /login` for accounts with two-factor authentication
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct TwoFactorChallenge {
+    pub two_factor_required: bool,
+    pub challenge_token: String,
+}
+
+#[cfg(test)]
+mod two_factor_test {
+    use super::{
+        generate_backup_codes, generate_secret, hash_backup_code, is_totp_code, otpauth_uri,
+        totp_code, verify_totp, BACKUP_CODE_COUNT, BASE32_NOPAD, TOTP_PERIOD,
+    };
+
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn rfc_6238_vectors() {
+        // SHA1 vectors of RFC 6238, truncated to 6 digits
+        for (time, code) in [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+        ] {
+            assert_eq!(totp_code(RFC_SECRET, time / TOTP_PERIOD), code, "{}", time);
+        }
+    }
+
+    #[test]
+    fn verify_within_window() {
+        let secret = BASE32_NOPAD.encode(RFC_SECRET);
+        assert_eq!(verify_totp(&secret, "081804", 1111111109), Some(37037036));
+        assert_eq!(verify_totp(&secret, "081804", 1111111109 + TOTP_PERIOD), Some(37037036));
+        assert_eq!(verify_totp(&secret, "081804", 1111111109 + 2 * TOTP_PERIOD), None);
+        assert_eq!(verify_totp("not base32!", "081804", 1111111109), None);
+    }
+
+    #[test]
+    fn generated_secrets_and_codes() {
+        let secret = generate_secret();
+        assert_eq!(BASE32_NOPAD.decode(secret.as_bytes()).unwrap().len(), 20);
+        assert!(otpauth_uri("jane doe", &secret)
+            .starts_with("otpauth://totp/Marketplace:jane%20doe?secret="));
+
+        let codes = generate_backup_codes();
+        assert_eq!(codes.len(), BACKUP_CODE_COUNT);
+        assert!(!is_totp_code(&codes[0]));
+        assert_eq!(hash_backup_code(&codes[0]), hash_backup_code(&codes[0].replace('-', "").to_uppercase()));
+    }
+}
@@ -0,0 +1,138 @@
+use handle_errors::{Error, FieldError};
+
+/// Implemented by every request body, checked before the body reaches a handler
+pub trait Validate {
+    /// Report every broken rule at once as an `Error::ValidationError`
+    fn validate(&self) -> Result<(), Error> {
+        let mut validator = Validator::default();
+        self.check(&mut validator);
+        validator.finish()
+    }
+
+    /// Record the rules broken by this value into `validator`
+    fn check(&self, validator: &mut Validator);
+}
+
+/// Collect field errors while a request body is being checked
+/// # Example usage
+/// ```rust
+/// use restful_api::types::validation::Validator;
+///
+/// let mut validator = Validator::default();
+/// validator.length("name", "", 1, 255);
+/// validator.check("price", -1 >= 0, "min", "must not be negative");
+/// assert_eq!(validator.errors().len(), 2);
+/// assert!(validator.finish().is_err());
+/// ```
+#[derive(Default, Debug)]
+pub struct Validator {
+    prefix: Vec<String>,
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    /// Record an error for `field` unless `valid` holds
+    pub fn check(&mut self, field: &str, valid: bool, rule: &str, message: &str) -> &mut Self {
+        if !valid {
+            let field = self
+                .prefix
+                .iter()
+                .map(String::as_str)
+                .chain(std::iter::once(field))
+                .collect::<Vec<_>>()
+                .join(".");
+            self.errors.push(FieldError {
+                field,
+                rule: rule.to_string(),
+                message: message.to_string(),
+            });
+        }
+        self
+    }
+
+    /// Check the number of characters of a string, surrounding whitespace excluded
+    pub fn length(&mut self, field: &str, value: &str, min: usize, max: usize) -> &mut Self {
+        let length = value.trim().chars().count();
+        self.check(
+            field,
+            (min..=max).contains(&length),
+            "length",
+            &format!("must be between {} and {} characters long", min, max),
+        )
+    }
+
+    /// Check a nested value, its errors are reported as `field.inner`
+    pub fn nested<T: Validate>(&mut self, field: &str, value: &T) -> &mut Self {
+        self.prefix.push(field.to_string());
+        value.check(self);
+        self.prefix.pop();
+        self
+    }
+
+    pub fn errors(&self) -> &[FieldError] {
+        &self.errors
+    }
+
+    pub fn finish(self) -> Result<(), Error> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::ValidationError(self.errors))
+        }
+    }
+}
+
+impl<T: Validate> Validate for Option<T> {
+    fn check(&self, validator: &mut Validator) {
+        if let Some(value) = self {
+            value.check(validator);
+        }
+    }
+}
+
+#[cfg(test)]
+mod validation_test {
+    use super::{Error, Validate, Validator};
+
+    struct Inner(i32);
+
+    impl Validate for Inner {
+        fn check(&self, validator: &mut Validator) {
+            validator.check("value", self.0 >= 0, "min", "must not be negative");
+        }
+    }
+
+    struct Outer {
+        name: String,
+        inner: Inner,
+    }
+
+    impl Validate for Outer {
+        fn check(&self, validator: &mut Validator) {
+            validator
+                .length("name", &self.name, 1, 3)
+                .nested("inner", &self.inner);
+        }
+    }
+
+    #[test]
+    fn valid_body() {
+        let body = Outer { name: "abc".to_string(), inner: Inner(1) };
+        assert!(body.validate().is_ok());
+    }
+
+    #[test]
+    fn every_field_is_reported() {
+        let body = Outer { name: "  ".to_string(), inner: Inner(-1) };
+        match body.validate().unwrap_err() {
+            Error::ValidationError(errors) => {
+                let fields: Vec<(&str, &str)> = errors
+                    .iter()
+                    .map(|e| (e.field.as_str(), e.rule.as_str()))
+                    .collect();
+                assert_eq!(fields, vec![("name", "length"), ("inner.value", "min")]);
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+}
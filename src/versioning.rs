@@ -0,0 +1,170 @@
+use chrono::prelude::*;
+use warp::http::header::{HeaderValue, LINK};
+use warp::path::FullPath;
+use warp::{Filter, Reply};
+
+use handle_errors::Error;
+
+use crate::config::Config;
+use crate::types::etag::http_date;
+
+/// Versions the API is mounted under, every version has its own routes built from the shared handlers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiVersion {
+    V1,
+}
+
+impl ApiVersion {
+    /// First path segment of the version, `/v1/products`
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "v1",
+        }
+    }
+
+    /// Serve `routes` under the prefix of the version
+    pub fn mount<F, R>(self, routes: F) -> impl Filter<Extract = (R,), Error = warp::Rejection> + Clone
+    where
+        F: Filter<Extract = (R,), Error = warp::Rejection> + Clone + Send + Sync + 'static,
+        R: Reply,
+    {
+        warp::path(self.prefix()).and(routes)
+    }
+}
+
+/// Midnight UTC of a `YYYY-MM-DD` configuration value
+fn config_date(name: &str, value: &str) -> Result<DateTime<Utc>, Error> {
+    NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+        .map(|date| date.and_time(NaiveTime::MIN).and_utc())
+        .map_err(|_| Error::InvalidConfig(format!("{} '{}' must be a YYYY-MM-DD date", name, value)))
+}
+
+/// The paths served before versioning, kept as aliases of a version until their sunset
+#[derive(Debug, Clone)]
+pub struct LegacyRoutes {
+    successor: ApiVersion,
+    deprecated_on: DateTime<Utc>,
+    sunset_on: DateTime<Utc>,
+}
+
+impl LegacyRoutes {
+    /*
+    @desc Read the deprecation and sunset of the unversioned paths from the configuration, the
+    unversioned paths alias /v1 as it is the version they were frozen into. A sunset already
+    passed is refused, clients would be told the paths they are using are gone
+     */
+    pub fn from_config(config: &Config) -> Result<Self, Error> {
+        let deprecated_on = config_date("Legacy routes deprecation", &config.legacy_routes_deprecated_on)?;
+        let sunset_on = config_date("Legacy routes sunset", &config.legacy_routes_sunset)?;
+        if sunset_on <= deprecated_on {
+            return Err(Error::InvalidConfig(format!(
+                "Legacy routes sunset must come after their deprecation on {}",
+                config.legacy_routes_deprecated_on
+            )));
+        }
+        if sunset_on <= Utc::now() {
+            return Err(Error::InvalidConfig(format!(
+                "Legacy routes sunset {} has already passed",
+                config.legacy_routes_sunset
+            )));
+        }
+
+        Ok(LegacyRoutes {
+            successor: ApiVersion::V1,
+            deprecated_on,
+            sunset_on,
+        })
+    }
+
+    /// `Deprecation` header as per RFC 9745, the unix time of the deprecation
+    pub fn deprecation_header(&self) -> String {
+        format!("@{}", self.deprecated_on.timestamp())
+    }
+
+    /// `Sunset` header as per RFC 8594, an HTTP date
+    pub fn sunset_header(&self) -> String {
+        http_date(&self.sunset_on)
+    }
+
+    /*
+    @desc Serve the routes of the successor version at the unversioned paths. Replies carry
+    `Deprecation` and `Sunset`, and a `Link` to the same path under the successor version
+     */
+    pub fn alias<F, R>(
+        &self,
+        routes: F,
+    ) -> impl Filter<Extract = (warp::reply::Response,), Error = warp::Rejection> + Clone
+    where
+        F: Filter<Extract = (R,), Error = warp::Rejection> + Clone + Send + Sync + 'static,
+        R: Reply,
+    {
+        let deprecation = HeaderValue::from_str(&self.deprecation_header()).expect("Deprecation is a header value");
+        let sunset = HeaderValue::from_str(&self.sunset_header()).expect("Sunset is a header value");
+        let prefix = self.successor.prefix();
+
+        warp::path::full()
+            .and(routes)
+            .map(move |path: FullPath, reply: R| {
+                let mut response = reply.into_response();
+                let headers = response.headers_mut();
+                headers.insert("deprecation", deprecation.clone());
+                headers.insert("sunset", sunset.clone());
+                let link = format!("</{}{}>; rel=\"successor-version\"", prefix, path.as_str());
+                if let Ok(link) = HeaderValue::from_str(&link) {
+                    headers.append(LINK, link);
+                }
+                response
+            })
+    }
+}
+
+#[cfg(test)]
+mod versioning_test {
+    use super::{config_date, ApiVersion, LegacyRoutes};
+    use crate::config::Config;
+    use warp::Filter;
+
+    fn legacy(sunset: &str) -> LegacyRoutes {
+        LegacyRoutes {
+            successor: ApiVersion::V1,
+            deprecated_on: config_date("Deprecation", "2024-06-11").unwrap(),
+            sunset_on: config_date("Sunset", sunset).unwrap(),
+        }
+    }
+
+    #[test]
+    fn sunset_must_be_ahead() {
+        let config = |deprecated_on: &str, sunset: &str| Config {
+            legacy_routes_deprecated_on: deprecated_on.to_string(),
+            legacy_routes_sunset: sunset.to_string(),
+            ..Config::default()
+        };
+        assert!(LegacyRoutes::from_config(&Config::default()).is_ok());
+        assert!(LegacyRoutes::from_config(&config("2024-06-11", "9999-01-01")).is_ok());
+        assert!(LegacyRoutes::from_config(&config("2024-06-11", "2025-01-01")).is_err());
+        assert!(LegacyRoutes::from_config(&config("9999-06-01", "9999-01-01")).is_err());
+    }
+
+    #[test]
+    fn deprecation_headers() {
+        let legacy = legacy("2025-01-01");
+        assert_eq!(legacy.deprecation_header(), "@1718064000");
+        assert_eq!(legacy.sunset_header(), "Wed, 01 Jan 2025 00:00:00 GMT");
+        assert!(config_date("Sunset", "01/01/2025").is_err());
+    }
+
+    #[tokio::test]
+    async fn aliases_point_at_their_successor() {
+        let routes = warp::path("products").and(warp::path::end()).map(|| "products");
+        let versioned = ApiVersion::V1.mount(routes);
+        assert!(warp::test::request().path("/v1/products").filter(&versioned).await.is_ok());
+        assert!(warp::test::request().path("/products").filter(&versioned).await.is_err());
+
+        let response = warp::test::request()
+            .path("/products?limit=1")
+            .reply(&legacy("2025-01-01").alias(routes))
+            .await;
+        assert_eq!(response.headers()["deprecation"], "@1718064000");
+        assert_eq!(response.headers()["link"], "</v1/products>; rel=\"successor-version\"");
+    }
+}